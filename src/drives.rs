@@ -3,7 +3,7 @@ pub mod drive_mod;
 
 use crate::drives::drive_mod::Drive;
 use std::ffi::OsString;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use sysinfo::{Disk, Disks};
 
 pub fn get_removable_disks() -> Vec<Drive> {
@@ -24,6 +24,14 @@ pub fn get_removable_disks() -> Vec<Drive> {
     drives
 }
 
+/// Free space of the drive mounted at `mount_point`, read again on each call.
+pub fn available_space(mount_point: &Path) -> Option<u64> {
+    Disks::new_with_refreshed_list()
+        .iter()
+        .find(|disk| disk.mount_point() == mount_point)
+        .map(Disk::available_space)
+}
+
 #[cfg(target_os = "windows")]
 fn get_disk_label(disk: &Disk) -> OsString {
     disk.name().to_os_string()
@@ -1,55 +1,195 @@
+mod history_dialog;
+mod queue;
+mod search;
+
 use crate::drives::drive_mod::{Drive, DriveList};
-use crate::drives::get_removable_disks;
-use crate::yt::{YoutubeDownloader, DEFAULT_LIB_DIR};
-use relm4::gtk::glib::{GString, clone};
+use crate::drives::{available_space, get_removable_disks};
+use crate::history::{DEFAULT_HISTORY_FILE, History, HistoryEntry};
+use crate::yt::filename::DEFAULT_OUTPUT_TEMPLATE;
+use crate::yt::loudness::{DEFAULT_TARGET_LUFS, MAX_TARGET_LUFS, MIN_TARGET_LUFS};
+use crate::yt::metadata::{VideoInfo, fetch_thumbnail};
+use crate::yt::playlist::{Playlist, PlaylistEvent, PlaylistReport};
+use crate::yt::progress::DownloadProgress;
+use crate::yt::sponsorblock::SponsorBlockCategory;
+use crate::yt::trim::TrimError;
+use crate::yt::url::YoutubeLink;
+use crate::yt::{
+    AudioFormat, AudioQuality, DownloadError, DownloadedFile, InstallProgress,
+    TrimRange, YoutubeDownloader, DEFAULT_LIB_DIR,
+};
+use history_dialog::{format_date, history_dialog};
+use queue::{DownloadJob, DownloadTarget, QueueItem, QueueItemOutput, QueueItemState};
+use search::SearchResult;
+use relm4::factory::FactoryVecDeque;
+use relm4::gtk::gdk;
+use relm4::gtk::glib::{self, GString, clone};
 use relm4::gtk::prelude::{BoxExt, ButtonExt, Cast, EditableExt, GtkWindowExt, WidgetExt};
 use relm4::{
     Component, ComponentParts, ComponentSender, RelmWidgetExt,
     adw, gtk,
 };
-use std::path::PathBuf;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use libadwaita::glib;
 use libadwaita::gtk::Orientation;
-use libadwaita::prelude::PreferencesGroupExt;
+use libadwaita::prelude::{AdwDialogExt, ComboRowExt, EntryRowExt, ExpanderRowExt, PreferencesGroupExt};
+use reqwest::Url;
+
+const DEFAULT_PARALLEL_DOWNLOADS: usize = 2;
+const MAX_PARALLEL_DOWNLOADS: usize = 6;
+const SEARCH_RESULT_COUNT: usize = 10;
+/// A tenth of the estimated size is kept free on the drive.
+const SPACE_MARGIN_DIVISOR: u64 = 10;
+const LINK_PAGE: &str = "link";
+const SEARCH_PAGE: &str = "search";
 
 #[derive(Debug, Clone)]
 pub enum Message {
     DriveSelection(Drive),
+    InputModeChanged(InputMode),
     LinkChanged(GString),
-    Save,
-    SwitchToNormal,
+    SearchQueryChanged(GString),
+    Search,
+    WholePlaylistToggled(bool),
+    TrimStartChanged(GString),
+    TrimEndChanged(GString),
+    AudioFormatSelected(AudioFormat),
+    AudioQualitySelected(AudioQuality),
+    EmbedTagsToggled(bool),
+    SkipArchivedToggled(bool),
+    NormalizeToggled(bool),
+    SplitChaptersToggled(bool),
+    SponsorBlockToggled(bool),
+    SponsorBlockCategoryToggled(SponsorBlockCategory, bool),
+    SponsorBlockApiChanged(GString),
+    VerifyWritesToggled(bool),
+    LoudnessTargetChanged(f64),
+    OutputTemplateChanged(GString),
+    ParallelDownloadsChanged(usize),
+    Enqueue,
+    EnqueueDuplicates,
+    RetryQueueItem(u64),
+    RemoveQueueItem(u64),
+    ShowHistory,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum CommandMessage {
+    PreDownloadProgress(InstallProgress),
     PreDownloadDone,
-    UpdateCheckDone,
-    DownloadFinished,
+    PreDownloadFailed(DownloadError),
+    /// A download found yt-dlp or ffmpeg missing from the libraries folder.
+    PrerequisiteMissing,
+    PreviewFetched { url: String, result: Result<VideoInfo, DownloadError> },
+    ThumbnailFetched { url: String, result: Result<Vec<u8>, DownloadError> },
+    SearchDone { search_id: u64, result: Result<Vec<VideoInfo>, DownloadError> },
+    SearchThumbnailFetched { search_id: u64, index: usize, result: Result<Vec<u8>, DownloadError> },
+    TrackStarted { id: u64, index: usize, count: usize, title: String },
+    DownloadProgress { id: u64, progress: DownloadProgress },
+    DownloadFinished { id: u64, summary: String, history: Vec<HistoryEntry> },
+    DownloadFailed { id: u64, reason: String, history: Vec<HistoryEntry> },
+    DownloadCancelled { id: u64, history: Vec<HistoryEntry> },
+}
+
+/// How the user picks the videos to download.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputMode {
+    Link,
+    Search,
+}
+
+enum SearchState {
+    Idle,
+    Searching,
+    Done,
+    Failed(String),
+}
+
+/// Preview of the video the user is about to add, `url` identifies the link it was fetched for.
+enum Preview {
+    Hidden,
+    Loading { url: String },
+    Ready { url: String, info: VideoInfo },
+    Failed { url: String, reason: String },
 }
 
 #[derive(PartialEq)]
 enum ConverterState {
     Normal,
     WrongLink,
+    WrongTrim(TrimError),
+    NotEnoughSpace(String),
     PreDownloading,
-    CheckingUpdate,
-    Downloading,
-    TransitionFromDownloadSuccess,
 }
 
 pub struct ConverterWidgets {
+    toast_overlay: adw::ToastOverlay,
     device_combo: gtk::DropDown,
     link_input: adw::EntryRow,
+    search_input: adw::EntryRow,
+    search_spinner: adw::Spinner,
+    search_status: gtk::Label,
+    search_window: gtk::ScrolledWindow,
+    preview_card: gtk::Box,
+    preview_picture: gtk::Picture,
+    preview_spinner: adw::Spinner,
+    preview_title: gtk::Label,
+    preview_channel: gtk::Label,
+    preview_details: gtk::Label,
+    whole_playlist_row: adw::SwitchRow,
+    trim_start_input: adw::EntryRow,
+    trim_end_input: adw::EntryRow,
+    audio_format_row: adw::ComboRow,
+    audio_quality_row: adw::ComboRow,
+    embed_tags_row: adw::SwitchRow,
+    skip_archived_row: adw::SwitchRow,
+    normalize_row: adw::SwitchRow,
+    loudness_target_row: adw::SpinRow,
+    split_chapters_row: adw::SwitchRow,
+    sponsorblock_row: adw::ExpanderRow,
+    sponsorblock_api_input: adw::EntryRow,
+    verify_writes_row: adw::SwitchRow,
+    output_template_input: adw::EntryRow,
     save_button: gtk::Button,
+    prerequisites_progress_bar: gtk::ProgressBar,
+    queue_window: gtk::ScrolledWindow,
+    queue_progress_bar: gtk::ProgressBar,
 }
 
 pub struct Converter {
     youtube: YoutubeDownloader,
-    update_checked: bool,
     selected_drive: Option<Drive>,
-    link: GString,
-    converter_state: ConverterState
+    /// `None` when one of the links typed by the user is not a YouTube link.
+    parsed_links: Option<Vec<YoutubeLink>>,
+    preview: Preview,
+    preview_thumbnail: Option<gdk::Texture>,
+    input_mode: InputMode,
+    search_query: String,
+    search_state: SearchState,
+    /// Identifies the last search, the results of the previous ones are dropped.
+    search_id: u64,
+    search_results: FactoryVecDeque<SearchResult>,
+    whole_playlist: bool,
+    trim_start: String,
+    trim_end: String,
+    audio_format: AudioFormat,
+    normalize: bool,
+    loudness_target: f64,
+    sponsorblock: bool,
+    sponsorblock_categories: Vec<SponsorBlockCategory>,
+    sponsorblock_api: String,
+    converter_state: ConverterState,
+    prerequisites_progress: InstallProgress,
+    /// Whether yt-dlp and ffmpeg were found installed, checked again only once one goes missing.
+    prerequisites_installed: bool,
+    queue: FactoryVecDeque<QueueItem>,
+    next_queue_id: u64,
+    max_parallel_downloads: usize,
+    history: History,
+    /// Links already downloaded to the drive, waiting for the user to confirm them.
+    duplicates: Option<(Drive, Vec<(DownloadTarget, HistoryEntry)>)>,
+    /// Problems to tell the user about, each shown once as a toast.
+    notices: Vec<String>,
 }
 
 impl Component for Converter {
@@ -84,6 +224,7 @@ impl Component for Converter {
         };
         let youtube = YoutubeDownloader::new(PathBuf::from(DEFAULT_LIB_DIR));
 
+        let toast_overlay = adw::ToastOverlay::new();
         let vbox = gtk::Box::builder()
             .orientation(gtk::Orientation::Vertical)
             .spacing(10)
@@ -97,18 +238,263 @@ impl Component for Converter {
             .list_factory(&DriveList::create_factory())
             .build();
 
-        let link_input = adw::EntryRow::builder().title("Lien Youtube").build();
+        let link_input = adw::EntryRow::builder()
+            .title("Liens Youtube, séparés par des espaces")
+            .build();
+        let whole_playlist_row = adw::SwitchRow::builder()
+            .title("Télécharger toute la playlist")
+            .visible(false)
+            .build();
+        let trim_start_input = adw::EntryRow::builder()
+            .title("Début, par exemple 0:30 (facultatif)")
+            .visible(false)
+            .build();
+        let trim_end_input = adw::EntryRow::builder()
+            .title("Fin, par exemple 3:45 (facultatif)")
+            .visible(false)
+            .build();
+        let audio_format_row = adw::ComboRow::builder()
+            .title("Format")
+            .model(&string_list(&AudioFormat::ALL))
+            .build();
+        let audio_quality_row = adw::ComboRow::builder()
+            .title("Qualité")
+            .model(&string_list(&AudioQuality::ALL))
+            .selected(
+                AudioQuality::ALL
+                    .iter()
+                    .position(|quality| *quality == AudioQuality::default())
+                    .unwrap_or_default() as u32,
+            )
+            .build();
+        let embed_tags_row = adw::SwitchRow::builder()
+            .title("Ajouter le titre, l'artiste et la pochette")
+            .active(true)
+            .build();
+        let skip_archived_row = adw::SwitchRow::builder()
+            .title("Ignorer les vidéos déjà présentes sur la clé")
+            .active(true)
+            .build();
+        let normalize_row = adw::SwitchRow::builder()
+            .title("Égaliser le volume des pistes")
+            .build();
+        let loudness_target_row = adw::SpinRow::builder()
+            .title("Volume cible (LUFS)")
+            .subtitle("Plus la valeur est proche de zéro, plus le son est fort")
+            .digits(1)
+            .adjustment(&gtk::Adjustment::new(
+                DEFAULT_TARGET_LUFS,
+                MIN_TARGET_LUFS,
+                MAX_TARGET_LUFS,
+                0.5,
+                1.0,
+                0.0,
+            ))
+            .visible(false)
+            .build();
+        let split_chapters_row = adw::SwitchRow::builder()
+            .title("Découper en pistes selon les chapitres")
+            .subtitle("Pour les albums complets et les mixes, une piste par chapitre dans un dossier")
+            .build();
+        let sponsorblock_row = adw::ExpanderRow::builder()
+            .title("Retirer les passages hors musique")
+            .subtitle("Sponsors, introductions, fins… signalés sur SponsorBlock")
+            .show_enable_switch(true)
+            .enable_expansion(false)
+            .build();
+        for category in SponsorBlockCategory::ALL {
+            let category_row = adw::SwitchRow::builder()
+                .title(category.to_string())
+                .active(category.is_removed_by_default())
+                .build();
+            category_row.connect_active_notify(clone!(
+                #[strong]
+                sender,
+                move |e| sender.input(Message::SponsorBlockCategoryToggled(category, e.is_active()))
+            ));
+            sponsorblock_row.add_row(&category_row);
+        }
+        let sponsorblock_api_input = adw::EntryRow::builder()
+            .title("Serveur SponsorBlock (facultatif)")
+            .build();
+        sponsorblock_row.add_row(&sponsorblock_api_input);
+        let verify_writes_row = adw::SwitchRow::builder()
+            .title("Vérifier les fichiers copiés sur la clé")
+            .subtitle("Relit chaque fichier après la copie, plus lent mais détecte les clés défectueuses")
+            .build();
+        let output_template_input = adw::EntryRow::builder()
+            .title("Nom des fichiers")
+            .text(DEFAULT_OUTPUT_TEMPLATE)
+            .build();
+        let parallel_downloads_row = adw::SpinRow::builder()
+            .title("Téléchargements simultanés")
+            .adjustment(&gtk::Adjustment::new(
+                DEFAULT_PARALLEL_DOWNLOADS as f64,
+                1.0,
+                MAX_PARALLEL_DOWNLOADS as f64,
+                1.0,
+                0.0,
+                0.0,
+            ))
+            .build();
+        let link_group = adw::PreferencesGroup::new();
+        link_group.add(&link_input);
+        link_group.add(&whole_playlist_row);
+        link_group.add(&trim_start_input);
+        link_group.add(&trim_end_input);
         let pref_group = adw::PreferencesGroup::new();
-        pref_group.add(&link_input);
+        pref_group.add(&audio_format_row);
+        pref_group.add(&audio_quality_row);
+        pref_group.add(&embed_tags_row);
+        pref_group.add(&skip_archived_row);
+        pref_group.add(&normalize_row);
+        pref_group.add(&loudness_target_row);
+        pref_group.add(&split_chapters_row);
+        pref_group.add(&sponsorblock_row);
+        pref_group.add(&verify_writes_row);
+        pref_group.add(&output_template_input);
+        pref_group.add(&parallel_downloads_row);
+        let preview_picture = gtk::Picture::builder()
+            .content_fit(gtk::ContentFit::Cover)
+            .width_request(128)
+            .height_request(72)
+            .build();
+        let preview_spinner = adw::Spinner::builder()
+            .width_request(128)
+            .height_request(72)
+            .build();
+        let preview_title = gtk::Label::builder()
+            .xalign(0.0)
+            .wrap(true)
+            .lines(2)
+            .ellipsize(gtk::pango::EllipsizeMode::End)
+            .css_classes(["heading"])
+            .build();
+        let preview_channel = gtk::Label::builder()
+            .xalign(0.0)
+            .ellipsize(gtk::pango::EllipsizeMode::End)
+            .css_classes(["dim-label"])
+            .build();
+        let preview_details = gtk::Label::builder()
+            .xalign(0.0)
+            .wrap(true)
+            .css_classes(["caption"])
+            .build();
+        let preview_text = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(3)
+            .valign(gtk::Align::Center)
+            .hexpand(true)
+            .build();
+        preview_text.append(&preview_title);
+        preview_text.append(&preview_channel);
+        preview_text.append(&preview_details);
+        let preview_card = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(10)
+            .css_classes(["card"])
+            .visible(false)
+            .build();
+        preview_card.append(&preview_picture);
+        preview_card.append(&preview_spinner);
+        preview_card.append(&preview_text);
+        let link_page = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(10)
+            .build();
+        link_page.append(&link_group);
+        link_page.append(&preview_card);
+
+        let search_input = adw::EntryRow::builder()
+            .title("Mots-clés")
+            .show_apply_button(true)
+            .build();
+        let search_group = adw::PreferencesGroup::new();
+        search_group.add(&search_input);
+        let search_spinner = adw::Spinner::builder()
+            .height_request(32)
+            .visible(false)
+            .build();
+        let search_status = gtk::Label::builder()
+            .wrap(true)
+            .visible(false)
+            .css_classes(["dim-label"])
+            .build();
+        let search_list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(["boxed-list"])
+            .build();
+        let search_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .propagate_natural_height(true)
+            .max_content_height(300)
+            .child(&search_list)
+            .visible(false)
+            .build();
+        let search_page = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(10)
+            .build();
+        search_page.append(&search_group);
+        search_page.append(&search_spinner);
+        search_page.append(&search_status);
+        search_page.append(&search_window);
+        let search_results = FactoryVecDeque::builder().launch(search_list).detach();
+
+        let input_stack = adw::ViewStack::new();
+        input_stack.add_titled_with_icon(&link_page, Some(LINK_PAGE), "Lien", "insert-link-symbolic");
+        input_stack.add_titled_with_icon(&search_page, Some(SEARCH_PAGE), "Recherche", "system-search-symbolic");
+        let input_switcher = adw::ViewSwitcher::builder()
+            .stack(&input_stack)
+            .policy(adw::ViewSwitcherPolicy::Wide)
+            .build();
         let save_button_content = adw::ButtonContent::builder()
-            .label("Enregistrer")
-            .icon_name("document-save")
+            .label("Ajouter")
+            .icon_name("list-add")
             .build();
         let save_button = gtk::Button::builder()
             .child(&save_button_content)
             .hexpand(false)
+            .build();
+        let history_button_content = adw::ButtonContent::builder()
+            .label("Historique")
+            .icon_name("document-open-recent")
+            .build();
+        let history_button = gtk::Button::builder()
+            .child(&history_button_content)
+            .build();
+        let button_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(10)
             .halign(gtk::Align::Center)
             .build();
+        button_box.append(&save_button);
+        button_box.append(&history_button);
+        let queue_list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(["boxed-list"])
+            .build();
+        let queue_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .propagate_natural_height(true)
+            .max_content_height(300)
+            .child(&queue_list)
+            .visible(false)
+            .build();
+        let prerequisites_progress_bar = gtk::ProgressBar::builder()
+            .show_text(true)
+            .visible(false)
+            .build();
+        let queue_progress_bar = gtk::ProgressBar::builder()
+            .show_text(true)
+            .visible(false)
+            .build();
+        let queue = FactoryVecDeque::builder()
+            .launch(queue_list)
+            .forward(sender.input_sender(), |output| match output {
+                QueueItemOutput::Retry(id) => Message::RetryQueueItem(id),
+                QueueItemOutput::Remove(id) => Message::RemoveQueueItem(id),
+            });
 
         device_combo.connect_selected_item_notify(clone!(
             #[strong]
@@ -126,31 +512,231 @@ impl Component for Converter {
             move |e| sender.input(Message::LinkChanged(e.text()))
         ));
 
+        link_input.connect_entry_activated(clone!(
+            #[strong]
+            sender,
+            move |_| sender.input(Message::Enqueue)
+        ));
+
+        input_stack.connect_visible_child_name_notify(clone!(
+            #[strong]
+            sender,
+            move |e| {
+                let input_mode = match e.visible_child_name().as_deref() {
+                    Some(SEARCH_PAGE) => InputMode::Search,
+                    _ => InputMode::Link,
+                };
+                sender.input(Message::InputModeChanged(input_mode))
+            }
+        ));
+
+        search_input.connect_changed(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::SearchQueryChanged(e.text()))
+        ));
+
+        search_input.connect_apply(clone!(
+            #[strong]
+            sender,
+            move |_| sender.input(Message::Search)
+        ));
+
+        search_input.connect_entry_activated(clone!(
+            #[strong]
+            sender,
+            move |_| sender.input(Message::Search)
+        ));
+
+        whole_playlist_row.connect_active_notify(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::WholePlaylistToggled(e.is_active()))
+        ));
+
+        trim_start_input.connect_changed(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::TrimStartChanged(e.text()))
+        ));
+
+        trim_end_input.connect_changed(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::TrimEndChanged(e.text()))
+        ));
+
+        audio_format_row.connect_selected_notify(clone!(
+            #[strong]
+            sender,
+            move |e| {
+                if let Some(audio_format) = AudioFormat::ALL.get(e.selected() as usize) {
+                    sender.input(Message::AudioFormatSelected(*audio_format))
+                }
+            }
+        ));
+
+        audio_quality_row.connect_selected_notify(clone!(
+            #[strong]
+            sender,
+            move |e| {
+                if let Some(audio_quality) = AudioQuality::ALL.get(e.selected() as usize) {
+                    sender.input(Message::AudioQualitySelected(*audio_quality))
+                }
+            }
+        ));
+
+        embed_tags_row.connect_active_notify(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::EmbedTagsToggled(e.is_active()))
+        ));
+
+        skip_archived_row.connect_active_notify(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::SkipArchivedToggled(e.is_active()))
+        ));
+
+        normalize_row.connect_active_notify(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::NormalizeToggled(e.is_active()))
+        ));
+
+        loudness_target_row.connect_value_notify(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::LoudnessTargetChanged(e.value()))
+        ));
+
+        split_chapters_row.connect_active_notify(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::SplitChaptersToggled(e.is_active()))
+        ));
+
+        verify_writes_row.connect_active_notify(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::VerifyWritesToggled(e.is_active()))
+        ));
+
+        sponsorblock_row.connect_enable_expansion_notify(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::SponsorBlockToggled(e.enables_expansion()))
+        ));
+
+        sponsorblock_api_input.connect_changed(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::SponsorBlockApiChanged(e.text()))
+        ));
+
+        output_template_input.connect_changed(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::OutputTemplateChanged(e.text()))
+        ));
+
+        parallel_downloads_row.connect_value_notify(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(Message::ParallelDownloadsChanged(e.value() as usize))
+        ));
+
         save_button.connect_clicked(clone!(
             #[strong]
             sender,
             move |_| {
-                sender.input(Message::Save);
+                sender.input(Message::Enqueue);
             }
         ));
 
-        window.set_child(Some(&vbox));
+        history_button.connect_clicked(clone!(
+            #[strong]
+            sender,
+            move |_| sender.input(Message::ShowHistory)
+        ));
+
+        window.set_child(Some(&toast_overlay));
+        toast_overlay.set_child(Some(&vbox));
         vbox.append(&device_combo);
+        vbox.append(&input_switcher);
+        vbox.append(&input_stack);
         vbox.append(&pref_group);
-        vbox.append(&save_button);
+        vbox.append(&button_box);
+        vbox.append(&prerequisites_progress_bar);
+        vbox.append(&queue_window);
+        vbox.append(&queue_progress_bar);
 
         let model = Converter {
             youtube,
-            update_checked: false,
             selected_drive,
-            link: GString::new(),
-            converter_state: ConverterState::Normal
+            parsed_links: None,
+            preview: Preview::Hidden,
+            preview_thumbnail: None,
+            input_mode: InputMode::Link,
+            search_query: String::new(),
+            search_state: SearchState::Idle,
+            search_id: 0,
+            search_results,
+            whole_playlist: false,
+            trim_start: String::new(),
+            trim_end: String::new(),
+            audio_format: AudioFormat::default(),
+            normalize: false,
+            loudness_target: DEFAULT_TARGET_LUFS,
+            sponsorblock: false,
+            sponsorblock_categories: SponsorBlockCategory::ALL
+                .into_iter()
+                .filter(SponsorBlockCategory::is_removed_by_default)
+                .collect(),
+            sponsorblock_api: String::new(),
+            converter_state: ConverterState::Normal,
+            prerequisites_progress: InstallProgress::default(),
+            prerequisites_installed: false,
+            queue,
+            next_queue_id: 0,
+            max_parallel_downloads: DEFAULT_PARALLEL_DOWNLOADS,
+            history: History::load(PathBuf::from(DEFAULT_HISTORY_FILE)),
+            duplicates: None,
+            notices: Vec::new(),
         };
 
         let widgets = ConverterWidgets {
+            toast_overlay,
             device_combo,
             link_input,
+            search_input,
+            search_spinner,
+            search_status,
+            search_window,
+            preview_card,
+            preview_picture,
+            preview_spinner,
+            preview_title,
+            preview_channel,
+            preview_details,
+            whole_playlist_row,
+            trim_start_input,
+            trim_end_input,
+            audio_format_row,
+            audio_quality_row,
+            embed_tags_row,
+            skip_archived_row,
+            normalize_row,
+            loudness_target_row,
+            split_chapters_row,
+            sponsorblock_row,
+            sponsorblock_api_input,
+            verify_writes_row,
+            output_template_input,
             save_button,
+            prerequisites_progress_bar,
+            queue_window,
+            queue_progress_bar,
         };
 
         ComponentParts { model, widgets }
@@ -161,52 +747,141 @@ impl Component for Converter {
             Message::DriveSelection(drive) => {
                 self.selected_drive = Some(drive);
             }
-            Message::LinkChanged(link) => {
-                self.link = link;
+            Message::LinkChanged(links) => {
+                self.parsed_links = links.split_whitespace().map(YoutubeLink::parse).collect();
+                self.converter_state = ConverterState::Normal;
+                self.refresh_preview(sender);
+            }
+            Message::InputModeChanged(input_mode) => {
+                self.input_mode = input_mode;
+                self.converter_state = ConverterState::Normal;
+            }
+            Message::SearchQueryChanged(query) => {
+                self.search_query = query.trim().to_string();
+                self.converter_state = ConverterState::Normal;
+            }
+            Message::Search => self.search(sender),
+            Message::WholePlaylistToggled(whole_playlist) => {
+                self.whole_playlist = whole_playlist;
+                // A whole playlist is neither previewed nor trimmed
+                self.refresh_preview(sender);
+            }
+            Message::TrimStartChanged(trim_start) => {
+                self.trim_start = trim_start.to_string();
+                self.converter_state = ConverterState::Normal;
+            }
+            Message::TrimEndChanged(trim_end) => {
+                self.trim_end = trim_end.to_string();
                 self.converter_state = ConverterState::Normal;
             }
-            Message::Save => {
-                if self.converter_state != ConverterState::TransitionFromDownloadSuccess {
-                    if let Some(drive) = self.selected_drive.clone() {
-                        if !self.link.is_empty()
-                            && (self.link.starts_with("https://www.youtube.com/watch?v=")
-                            || self.link.starts_with("https://youtube.com/watch?v="))
-                        {
-                            self.converter_state = ConverterState::Downloading;
-
-                            if !self.youtube.check_prerequisites() {
-                                self.converter_state = ConverterState::PreDownloading;
-                                self.update_checked = true;
-                                sender.oneshot_command(async {
-                                    YoutubeDownloader::download_prerequisites(PathBuf::from(DEFAULT_LIB_DIR)).await;
-                                    CommandMessage::PreDownloadDone
-                                });
-                            } else if !self.update_checked {
-                                self.converter_state = ConverterState::CheckingUpdate;
-                                self.update_checked = true;
-                                sender.spawn_oneshot_command(move || {
-                                    YoutubeDownloader::check_update().wait().unwrap();
-                                    CommandMessage::UpdateCheckDone
-                                });
-                            } else {
-                                let output_dir = drive.mount_point();
-                                let mut youtube = self.youtube.clone();
-                                let link = self.link.clone().to_string();
-
-                                sender.spawn_oneshot_command(move || {
-                                    youtube.download(link, &output_dir).wait().unwrap();
-                                    CommandMessage::DownloadFinished
-                                });
+            Message::AudioFormatSelected(audio_format) => {
+                self.audio_format = audio_format;
+                self.youtube.set_audio_format(audio_format);
+            }
+            Message::AudioQualitySelected(audio_quality) => {
+                self.youtube.set_audio_quality(audio_quality);
+            }
+            Message::EmbedTagsToggled(embed_tags) => {
+                self.youtube.set_embed_metadata(embed_tags);
+                self.youtube.set_embed_cover_art(embed_tags);
+            }
+            Message::SkipArchivedToggled(skip_archived) => {
+                self.youtube.set_use_download_archive(skip_archived);
+            }
+            Message::NormalizeToggled(normalize) => {
+                self.normalize = normalize;
+                self.youtube.set_loudness_target(normalize.then_some(self.loudness_target));
+            }
+            Message::SplitChaptersToggled(split_chapters) => {
+                self.youtube.set_split_chapters(split_chapters);
+            }
+            Message::VerifyWritesToggled(verify_writes) => {
+                self.youtube.set_verify_writes(verify_writes);
+            }
+            Message::SponsorBlockToggled(sponsorblock) => {
+                self.sponsorblock = sponsorblock;
+                self.apply_sponsorblock();
+            }
+            Message::SponsorBlockCategoryToggled(category, removed) => {
+                self.sponsorblock_categories.retain(|c| *c != category);
+                if removed {
+                    self.sponsorblock_categories.push(category);
+                }
+                self.apply_sponsorblock();
+            }
+            Message::SponsorBlockApiChanged(api_url) => {
+                self.sponsorblock_api = api_url.trim().to_string();
+                self.apply_sponsorblock();
+            }
+            Message::LoudnessTargetChanged(loudness_target) => {
+                self.loudness_target = loudness_target;
+                self.youtube.set_loudness_target(self.normalize.then_some(loudness_target));
+            }
+            Message::OutputTemplateChanged(output_template) => {
+                let output_template = match output_template.trim() {
+                    "" => DEFAULT_OUTPUT_TEMPLATE,
+                    output_template => output_template,
+                };
+                self.youtube.set_output_template(output_template.to_string());
+            }
+            Message::ParallelDownloadsChanged(max_parallel_downloads) => {
+                // Lowering the limit lets the running downloads finish, no new one starts until then
+                self.max_parallel_downloads = max_parallel_downloads.max(1);
+                self.process_queue(sender);
+            }
+            Message::Enqueue => {
+                if let Err(e) = self.trim_range() {
+                    self.converter_state = ConverterState::WrongTrim(e);
+                } else if let Some(drive) = self.selected_drive.clone() {
+                    match self.download_targets() {
+                        Some(targets) if !targets.is_empty() => {
+                            let mut new_targets = Vec::new();
+                            let mut duplicates = Vec::new();
+                            for target in targets {
+                                match self.downloaded_before(&target, &drive).cloned() {
+                                    Some(entry) => duplicates.push((target, entry)),
+                                    None => new_targets.push(target),
+                                }
+                            }
+
+                            self.duplicates = Some((drive.clone(), duplicates))
+                                .filter(|(_, duplicates)| !duplicates.is_empty());
+                            self.enqueue(new_targets, self.youtube.clone(), &drive, sender);
+                            if self.input_mode == InputMode::Search {
+                                let mut search_results = self.search_results.guard();
+                                for index in 0..search_results.len() {
+                                    if let Some(result) = search_results.get_mut(index) {
+                                        result.deselect();
+                                    }
+                                }
                             }
-                        } else {
-                            self.converter_state = ConverterState::WrongLink;
                         }
-                    } else {
-                        println!("TODO: Handle no drive connected or selected")
+                        _ => self.converter_state = ConverterState::WrongLink,
                     }
+                } else {
+                    self.notices.push(String::from("Aucune clé sélectionnée"));
                 }
             }
-            Message::SwitchToNormal => self.converter_state = ConverterState::Normal
+            Message::EnqueueDuplicates => {
+                if let Some((drive, duplicates)) = self.duplicates.take() {
+                    // The archive of the drive would make yt-dlp skip them again
+                    let mut youtube = self.youtube.clone();
+                    youtube.set_use_download_archive(false);
+                    let targets = duplicates.into_iter().map(|(target, _)| target).collect();
+                    self.enqueue(targets, youtube, &drive, sender);
+                }
+            }
+            Message::RetryQueueItem(id) => {
+                self.update_queue_item(id, |item| item.set_state(QueueItemState::Pending));
+                self.process_queue(sender);
+            }
+            Message::RemoveQueueItem(id) => {
+                if let Some(index) = self.queue_index(id) {
+                    self.queue.guard().remove(index);
+                }
+            }
+            // The dialog is shown by update_with_view, it needs the window
+            Message::ShowHistory => {}
         }
     }
 
@@ -214,60 +889,676 @@ impl Component for Converter {
         &mut self,
         message: Self::CommandOutput,
         sender: ComponentSender<Self>,
-        root: &Self::Root,
+        _root: &Self::Root,
     ) {
         match message {
-            CommandMessage::PreDownloadDone | CommandMessage::UpdateCheckDone => {
-                self.update(Message::Save, sender, root);
+            CommandMessage::PreDownloadProgress(progress) => {
+                self.prerequisites_progress = progress;
+            }
+            CommandMessage::PreDownloadDone => {
+                self.converter_state = ConverterState::Normal;
+                self.prerequisites_installed = true;
+                // The preview and the search could not be made without yt-dlp
+                self.refresh_preview(sender.clone());
+                if matches!(self.search_state, SearchState::Searching) {
+                    self.search(sender.clone());
+                }
             }
-            CommandMessage::DownloadFinished => {
-                self.converter_state = ConverterState::TransitionFromDownloadSuccess;
+            CommandMessage::PreviewFetched { url, result } => {
+                if self.preview.url() == Some(url.as_str()) {
+                    self.preview = match result {
+                        Ok(info) => {
+                            let thumbnail_url = info.thumbnail_url();
+                            let url = url.clone();
+                            sender.oneshot_command(async move {
+                                CommandMessage::ThumbnailFetched {
+                                    url,
+                                    result: fetch_thumbnail(&thumbnail_url).await,
+                                }
+                            });
+                            Preview::Ready { url, info }
+                        }
+                        // The preview comes back once the prerequisites are downloaded
+                        Err(DownloadError::MissingPrerequisite(_)) => {
+                            self.prerequisites_installed = false;
+                            Preview::Hidden
+                        }
+                        Err(e) => Preview::Failed { url, reason: e.to_string() },
+                    };
+                }
+            }
+            CommandMessage::SearchDone { search_id, result } if search_id == self.search_id => {
+                match result {
+                    Ok(results) => {
+                        let mut search_results = self.search_results.guard();
+                        for (index, info) in results.into_iter().enumerate() {
+                            let thumbnail_url = info.thumbnail_url();
+                            sender.oneshot_command(async move {
+                                CommandMessage::SearchThumbnailFetched {
+                                    search_id,
+                                    index,
+                                    result: fetch_thumbnail(&thumbnail_url).await,
+                                }
+                            });
+                            search_results.push_back(SearchResult::new(info));
+                        }
+                        self.search_state = SearchState::Done;
+                    }
+                    Err(e) => {
+                        if matches!(e, DownloadError::MissingPrerequisite(_)) {
+                            self.prerequisites_installed = false;
+                        }
+                        self.search_state = SearchState::Failed(e.to_string());
+                    }
+                }
+            }
+            CommandMessage::SearchThumbnailFetched { search_id, index, result } if search_id == self.search_id => {
+                let thumbnail = result
+                    .ok()
+                    .and_then(|thumbnail| gdk::Texture::from_bytes(&glib::Bytes::from_owned(thumbnail)).ok());
+                if let (Some(thumbnail), Some(search_result)) = (thumbnail, self.search_results.guard().get_mut(index)) {
+                    search_result.set_thumbnail(thumbnail);
+                }
+            }
+            // A newer search replaced them
+            CommandMessage::SearchDone { .. } | CommandMessage::SearchThumbnailFetched { .. } => {}
+            CommandMessage::ThumbnailFetched { url, result } => {
+                if self.preview.url() == Some(url.as_str()) {
+                    // A missing thumbnail is not worth bothering the user
+                    self.preview_thumbnail = result
+                        .ok()
+                        .and_then(|thumbnail| gdk::Texture::from_bytes(&glib::Bytes::from_owned(thumbnail)).ok());
+                }
+            }
+            CommandMessage::PreDownloadFailed(e) => {
+                self.converter_state = ConverterState::Normal;
+                // Without yt-dlp nothing can be downloaded, the user retries each link when ready
+                let mut queue = self.queue.guard();
+                for index in 0..queue.len() {
+                    if let Some(item) = queue.get_mut(index).filter(|item| item.is_pending()) {
+                        item.set_state(QueueItemState::Failed(e.to_string()));
+                    }
+                }
+                if matches!(self.search_state, SearchState::Searching) {
+                    self.search_state = SearchState::Failed(e.to_string());
+                }
+            }
+            CommandMessage::PrerequisiteMissing => {
+                self.prerequisites_installed = false;
+            }
+            CommandMessage::TrackStarted { id, index, count, title } => {
+                self.update_queue_item(id, |item| item.set_track(index, count, title));
+            }
+            CommandMessage::DownloadProgress { id, progress } => {
+                self.update_queue_item(id, |item| item.set_progress(progress));
+            }
+            CommandMessage::DownloadFinished { id, summary, history } => {
+                self.record_history(history);
+                self.update_queue_item(id, |item| item.set_state(QueueItemState::Done(summary)));
+            }
+            CommandMessage::DownloadFailed { id, reason, history } => {
+                self.record_history(history);
+                self.update_queue_item(id, |item| item.set_state(QueueItemState::Failed(reason)));
+            }
+            CommandMessage::DownloadCancelled { id, history } => {
+                self.record_history(history);
+                self.update_queue_item(id, |item| {
+                    item.set_state(QueueItemState::Failed(DownloadError::Cancelled.to_string()))
+                });
             }
         }
+
+        self.process_queue(sender);
     }
 
-    fn update_view(&self, widgets: &mut Self::Widgets, sender: ComponentSender<Self>) {
-        match self.converter_state {
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        let queue_len = self.queue.len();
+        let enqueue = matches!(message, Message::Enqueue);
+        let enqueue_duplicates = matches!(message, Message::EnqueueDuplicates);
+        let show_history = matches!(message, Message::ShowHistory);
+        self.update(message, sender.clone(), root);
+
+        // The links were added to the queue, the entry is ready for the next ones
+        if self.queue.len() > queue_len {
+            widgets.link_input.set_text("");
+            widgets.trim_start_input.set_text("");
+            widgets.trim_end_input.set_text("");
+        }
+        let refusal = match &self.converter_state {
+            ConverterState::WrongTrim(e) => Some(e.to_string()),
+            ConverterState::NotEnoughSpace(reason) => Some(reason.clone()),
+            _ => None,
+        };
+        if let (true, Some(refusal)) = (enqueue || enqueue_duplicates, refusal) {
+            let toast = adw::Toast::builder().title(refusal).use_markup(false).build();
+            widgets.toast_overlay.add_toast(toast);
+        }
+        if let (true, Some((drive, duplicates))) = (enqueue, &self.duplicates) {
+            widgets.toast_overlay.add_toast(duplicates_toast(drive, duplicates, &sender));
+        }
+        self.show_notices(widgets);
+        if show_history {
+            history_dialog(&self.history).present(Some(root));
+        }
+        self.update_view(widgets, sender);
+    }
+
+    fn update_cmd_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::CommandOutput,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        if let CommandMessage::PreDownloadFailed(e) = &message {
+            let toast = adw::Toast::builder()
+                .title(e.to_string())
+                .use_markup(false)
+                .timeout(0)
+                .build();
+            widgets.toast_overlay.add_toast(toast);
+        }
+
+        self.update_cmd(message, sender.clone(), root);
+        self.show_notices(widgets);
+        self.update_view(widgets, sender);
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, _sender: ComponentSender<Self>) {
+        widgets.whole_playlist_row.set_visible(
+            self.parsed_links
+                .iter()
+                .flatten()
+                .any(YoutubeLink::is_video_in_playlist),
+        );
+        widgets.audio_quality_row.set_visible(!self.audio_format.is_lossless());
+        widgets.loudness_target_row.set_visible(self.normalize);
+        if !self.sponsorblock_api.is_empty() && self.sponsorblock_api_url().is_none() {
+            widgets.sponsorblock_api_input.add_css_class("error");
+        } else {
+            widgets.sponsorblock_api_input.remove_css_class("error");
+        }
+        self.update_trim(widgets);
+        self.update_preview(widgets);
+        self.update_search(widgets);
+        widgets.queue_window.set_visible(!self.queue.is_empty());
+        self.update_queue_progress_bar(widgets);
+        self.update_prerequisites_progress_bar(widgets);
+
+        match &self.converter_state {
             ConverterState::Normal => {
                 let save_button_content = adw::ButtonContent::builder()
-                    .label("Enregistrer")
-                    .icon_name("document-save")
+                    .label("Ajouter")
+                    .icon_name("list-add")
                     .build();
                 widgets.save_button.set_child(Some(&save_button_content));
                 widgets.save_button.set_sensitive(true);
-                widgets.device_combo.set_sensitive(true);
-                widgets.link_input.set_sensitive(true);
+                set_inputs_sensitive(widgets, true);
                 widgets.link_input.remove_css_class("error");
+                widgets.search_input.remove_css_class("error");
             }
-            ConverterState::WrongLink => {
-                widgets.link_input.add_css_class("error");
-            }
+            // The faulty timestamp is already highlighted, the refusals are shown as toasts
+            ConverterState::WrongTrim(_) | ConverterState::NotEnoughSpace(_) => {}
+            // In search mode, no result was ticked
+            ConverterState::WrongLink => match self.input_mode {
+                InputMode::Link => widgets.link_input.add_css_class("error"),
+                InputMode::Search => widgets.search_input.add_css_class("error"),
+            },
             ConverterState::PreDownloading => {
                 self.set_button_loading_text(widgets, "Téléchargement des prérequis");
             }
-            ConverterState::CheckingUpdate => {
-                self.set_button_loading_text(widgets, "Vérification des mises à jour");
+        }
+    }
+}
+
+impl Converter {
+    fn show_notices(&mut self, widgets: &ConverterWidgets) {
+        for notice in self.notices.drain(..) {
+            let toast = adw::Toast::builder().title(notice).use_markup(false).build();
+            widgets.toast_overlay.add_toast(toast);
+        }
+    }
+
+    /// A playlist is only downloaded as a whole when the link does not point to one of its videos,
+    /// or when the user asked for it.
+    fn download_targets(&self) -> Option<Vec<DownloadTarget>> {
+        if self.input_mode == InputMode::Search {
+            return Some(self.selected_search_targets());
+        }
+
+        let trim = self.trim_range().ok().flatten();
+        self.parsed_links
+            .as_ref()?
+            .iter()
+            .map(|link| match (link.video_url(), link.playlist_url()) {
+                (Some(_), Some(playlist)) if self.whole_playlist => Some(DownloadTarget::Playlist(playlist)),
+                (Some(url), _) => {
+                    let id = link.video_id()?.to_string();
+                    let title = match &self.preview {
+                        Preview::Ready { info, .. } if info.id == id => Some(info.title.clone()),
+                        _ => None,
+                    };
+                    Some(DownloadTarget::Video { id, url, title, trim })
+                }
+                (None, Some(playlist)) => Some(DownloadTarget::Playlist(playlist)),
+                (None, None) => None,
+            })
+            .collect()
+    }
+
+    fn apply_sponsorblock(&mut self) {
+        let categories = if self.sponsorblock {
+            self.sponsorblock_categories.clone()
+        } else {
+            Vec::new()
+        };
+        self.youtube.set_sponsorblock_remove(categories);
+        self.youtube.set_sponsorblock_api(self.sponsorblock_api_url().map(String::from));
+    }
+
+    /// The SponsorBlock server typed by the user, `None` for the public one or an invalid address.
+    fn sponsorblock_api_url(&self) -> Option<Url> {
+        Url::parse(&self.sponsorblock_api)
+            .ok()
+            .filter(|url| url.scheme() == "http" || url.scheme() == "https")
+    }
+
+    /// The link typed by the user when there is a single one, and it is downloaded as a video.
+    /// Only such a link is previewed and can be trimmed.
+    fn single_video_link(&self) -> Option<&YoutubeLink> {
+        match self.parsed_links.as_deref() {
+            Some([link]) if link.video_url().is_some() && !(self.whole_playlist && link.is_video_in_playlist()) => {
+                Some(link)
             }
-            ConverterState::Downloading => {
-                self.set_button_loading_text(widgets, "Téléchargement du MP3");
+            _ => None,
+        }
+    }
+
+    /// Range of the video to download, checked against its duration once the preview knows it.
+    /// Always `None` when the link cannot be trimmed.
+    fn trim_range(&self) -> Result<Option<TrimRange>, TrimError> {
+        if self.input_mode == InputMode::Search || self.single_video_link().is_none() {
+            return Ok(None);
+        }
+
+        let trim = TrimRange::parse(&self.trim_start, &self.trim_end)?;
+        if let (Some(trim), Preview::Ready { info, .. }) = (&trim, &self.preview) {
+            if let Some(duration) = info.duration() {
+                trim.validate(duration)?;
             }
-            ConverterState::TransitionFromDownloadSuccess => {
-                widgets.save_button.add_css_class("success");
-                widgets.save_button.set_child(Some(&gtk::Label::new(Some("Succès !"))));
-                widgets.save_button.set_sensitive(true);
-                let cloned_btn = widgets.save_button.clone();
-                let cloned_sender = sender.clone();
-                glib::timeout_add_local(Duration::from_secs(2), move || {
-                    cloned_btn.remove_css_class("success");
-                    cloned_sender.input_sender().send(Message::SwitchToNormal).unwrap();
-                    glib::ControlFlow::Break
+        }
+
+        Ok(trim)
+    }
+
+    fn update_trim(&self, widgets: &mut ConverterWidgets) {
+        let visible = self.single_video_link().is_some();
+        widgets.trim_start_input.set_visible(visible);
+        widgets.trim_end_input.set_visible(visible);
+
+        let (start_error, end_error) = match self.trim_range() {
+            Err(TrimError::InvalidStart | TrimError::StartAfterDuration) => (true, false),
+            Err(TrimError::InvalidEnd | TrimError::EndAfterDuration) => (false, true),
+            Err(TrimError::Empty) => (true, true),
+            Ok(_) => (false, false),
+        };
+        for (input, error) in [(&widgets.trim_start_input, start_error), (&widgets.trim_end_input, end_error)] {
+            if error {
+                input.add_css_class("error");
+            } else {
+                input.remove_css_class("error");
+            }
+        }
+    }
+
+    /// Fetches the information of the link typed by the user, when there is a single one
+    /// pointing to a video.
+    fn refresh_preview(&mut self, sender: ComponentSender<Self>) {
+        let url = self.single_video_link().and_then(YoutubeLink::video_url);
+        if url.as_deref() == self.preview.url() {
+            return;
+        }
+
+        self.preview_thumbnail = None;
+        self.preview = match url {
+            Some(url) => {
+                let youtube = self.youtube.clone();
+                let fetched_url = url.clone();
+                sender.spawn_oneshot_command(move || CommandMessage::PreviewFetched {
+                    result: youtube.fetch_video_info(&fetched_url),
+                    url: fetched_url,
                 });
+                Preview::Loading { url }
+            }
+            None => Preview::Hidden,
+        };
+    }
+
+    fn update_search(&self, widgets: &mut ConverterWidgets) {
+        widgets.search_spinner.set_visible(matches!(self.search_state, SearchState::Searching));
+        widgets.search_window.set_visible(!self.search_results.is_empty());
+        widgets.search_status.remove_css_class("error");
+
+        let status = match &self.search_state {
+            SearchState::Idle | SearchState::Searching => None,
+            SearchState::Done if self.search_results.is_empty() => Some("Aucun résultat"),
+            SearchState::Done => None,
+            SearchState::Failed(reason) => {
+                widgets.search_status.add_css_class("error");
+                Some(reason.as_str())
+            }
+        };
+        widgets.search_status.set_visible(status.is_some());
+        widgets.search_status.set_label(status.unwrap_or_default());
+    }
+
+    fn update_preview(&self, widgets: &mut ConverterWidgets) {
+        widgets.preview_card.set_visible(!matches!(self.preview, Preview::Hidden));
+        widgets.preview_spinner.set_visible(matches!(self.preview, Preview::Loading { .. }));
+        widgets.preview_picture.set_visible(!matches!(self.preview, Preview::Loading { .. }));
+        widgets.preview_picture.set_paintable(self.preview_thumbnail.as_ref());
+        widgets.preview_title.remove_css_class("error");
+
+        match &self.preview {
+            Preview::Hidden => {}
+            Preview::Loading { .. } => {
+                widgets.preview_title.set_label("Recherche de la vidéo…");
+                widgets.preview_channel.set_label("");
+                widgets.preview_details.set_label("");
+            }
+            Preview::Ready { info, .. } => {
+                let mut details = Vec::new();
+                if let Some(duration) = info.duration() {
+                    details.push(format_duration(duration));
+                }
+                if let Some(size) = info.estimated_size() {
+                    details.push(format!("environ {}", format_size(size)));
+                }
+
+                widgets.preview_title.set_label(&info.title);
+                widgets.preview_channel.set_label(info.channel().unwrap_or_default());
+                widgets.preview_details.set_label(&details.join(" — "));
+            }
+            Preview::Failed { reason, .. } => {
+                widgets.preview_title.set_label("Vidéo introuvable");
+                widgets.preview_title.add_css_class("error");
+                widgets.preview_channel.set_label("");
+                widgets.preview_details.set_label(reason);
             }
         }
     }
-}
 
-impl Converter {
+    /// Successful download of the video to `drive`, playlists are never reported.
+    fn downloaded_before(&self, target: &DownloadTarget, drive: &Drive) -> Option<&HistoryEntry> {
+        match target {
+            DownloadTarget::Video { id, .. } => self.history.find(id, &drive.name(), &drive.mount_point()),
+            DownloadTarget::Playlist(_) => None,
+        }
+    }
+
+    fn enqueue(
+        &mut self,
+        targets: Vec<DownloadTarget>,
+        youtube: YoutubeDownloader,
+        drive: &Drive,
+        sender: ComponentSender<Self>,
+    ) {
+        if matches!(self.converter_state, ConverterState::NotEnoughSpace(_)) {
+            self.converter_state = ConverterState::Normal;
+        }
+        if targets.is_empty() {
+            return;
+        }
+
+        let estimated_sizes: Vec<Option<u64>> =
+            targets.iter().map(|target| self.estimated_size(target, &youtube)).collect();
+        if let Err(e) = self.check_space(&estimated_sizes, drive) {
+            self.converter_state = ConverterState::NotEnoughSpace(e.to_string());
+            return;
+        }
+        // Playlists are checked once listed, videos added without preview are not checked at all
+        let unknown_sizes = targets
+            .iter()
+            .zip(&estimated_sizes)
+            .filter(|(target, size)| matches!(target, DownloadTarget::Video { .. }) && size.is_none())
+            .count();
+        match unknown_sizes {
+            0 => {}
+            1 => self
+                .notices
+                .push(String::from("La taille de la vidéo n'est pas connue, la place sur la clé n'a pas été vérifiée")),
+            count => self.notices.push(format!(
+                "La taille de {} vidéos n'est pas connue, la place sur la clé n'a pas été vérifiée",
+                count
+            )),
+        }
+
+        let mut queue = self.queue.guard();
+        for (target, estimated_size) in targets.into_iter().zip(estimated_sizes) {
+            queue.push_back(QueueItem::new(self.next_queue_id, target, youtube.clone(), drive, estimated_size));
+            self.next_queue_id += 1;
+        }
+        drop(queue);
+
+        self.process_queue(sender);
+    }
+
+    /// Size of the file a video would give, from the duration found by its preview or the search.
+    /// Playlists are only checked once listed, when their download starts.
+    fn estimated_size(&self, target: &DownloadTarget, youtube: &YoutubeDownloader) -> Option<u64> {
+        let DownloadTarget::Video { id, .. } = target else {
+            return None;
+        };
+        let info = match &self.preview {
+            Preview::Ready { info, .. } if info.id == *id => Some(info),
+            _ => self
+                .search_results
+                .iter()
+                .map(SearchResult::info)
+                .find(|info| info.id == *id),
+        }?;
+
+        info.duration()
+            .map(|duration| youtube.estimated_size(duration))
+            .or(info.estimated_size())
+    }
+
+    /// Refuses downloads that cannot fit on `drive` along with those already waiting for it.
+    fn check_space(&self, estimated_sizes: &[Option<u64>], drive: &Drive) -> Result<(), DownloadError> {
+        let needed = self.queued_size(&drive.mount_point(), None) + estimated_sizes.iter().flatten().sum::<u64>();
+
+        check_space_for(needed, &drive.mount_point())
+    }
+
+    /// Estimated size of the unfinished items going to the drive mounted at `output_dir`,
+    /// the item `excluded` left out.
+    fn queued_size(&self, output_dir: &Path, excluded: Option<u64>) -> u64 {
+        self.queue
+            .iter()
+            .filter(|item| !item.is_finished() && item.output_dir() == output_dir && Some(item.id()) != excluded)
+            .filter_map(QueueItem::estimated_size)
+            .sum()
+    }
+
+    fn record_history(&mut self, entries: Vec<HistoryEntry>) {
+        if let Err(e) = self.history.add(entries) {
+            // Without the history, the next downloads are not checked against this one
+            self.notices.push(format!("Impossible d'enregistrer l'historique : {}", e));
+        }
+    }
+
+    fn queue_index(&self, id: u64) -> Option<usize> {
+        self.queue.iter().position(|item| item.id() == id)
+    }
+
+    fn update_queue_item(&mut self, id: u64, update: impl FnOnce(&mut QueueItem)) {
+        if let Some(index) = self.queue_index(id) {
+            if let Some(item) = self.queue.guard().get_mut(index) {
+                update(item);
+            }
+        }
+    }
+
+    /// Installs the prerequisites pinned in the bundled manifest when another version is installed,
+    /// then starts pending items until `max_parallel_downloads` of them are running. yt-dlp is never
+    /// updated by itself, only a new manifest moves it to another version.
+    fn process_queue(&mut self, sender: ComponentSender<Self>) {
+        if matches!(self.converter_state, ConverterState::PreDownloading)
+            || !self.queue.iter().any(QueueItem::is_pending)
+        {
+            return;
+        }
+
+        if !self.prerequisites_installed() {
+            self.download_prerequisites(sender);
+        } else {
+            while self.queue.iter().filter(|item| item.is_downloading()).count() < self.max_parallel_downloads {
+                let Some(index) = self.next_pending_index() else {
+                    break;
+                };
+                let Some(item) = self.queue.get(index) else {
+                    break;
+                };
+                let reserved_space = self.queued_size(item.output_dir(), Some(item.id()));
+                if let Some(item) = self.queue.guard().get_mut(index) {
+                    start_download(item.start(reserved_space), sender.clone());
+                }
+            }
+        }
+    }
+
+    /// Whether yt-dlp and ffmpeg are installed. The bundled manifest and the installed versions are
+    /// only read until they are found, then again once a download reports one of them missing.
+    fn prerequisites_installed(&mut self) -> bool {
+        if !self.prerequisites_installed {
+            self.prerequisites_installed = self.youtube.check_prerequisites();
+        }
+        self.prerequisites_installed
+    }
+
+    fn download_prerequisites(&mut self, sender: ComponentSender<Self>) {
+        self.converter_state = ConverterState::PreDownloading;
+        self.prerequisites_progress = InstallProgress::default();
+        sender.command(|out, shutdown| {
+            shutdown
+                .register(async move {
+                    let result = YoutubeDownloader::download_prerequisites(PathBuf::from(DEFAULT_LIB_DIR), |progress| {
+                        out.emit(CommandMessage::PreDownloadProgress(progress))
+                    })
+                    .await;
+                    out.emit(match result {
+                        Ok(()) => CommandMessage::PreDownloadDone,
+                        Err(e) => CommandMessage::PreDownloadFailed(e),
+                    });
+                })
+                .drop_on_shutdown()
+        });
+    }
+
+    /// Searches the keywords typed by the user, once yt-dlp is installed.
+    fn search(&mut self, sender: ComponentSender<Self>) {
+        if self.search_query.is_empty() {
+            return;
+        }
+
+        self.search_id += 1;
+        self.search_state = SearchState::Searching;
+        self.search_results.guard().clear();
+        if !self.prerequisites_installed() {
+            // The search starts again once the prerequisites are downloaded
+            if self.converter_state != ConverterState::PreDownloading {
+                self.download_prerequisites(sender);
+            }
+            return;
+        }
+
+        let youtube = self.youtube.clone();
+        let query = self.search_query.clone();
+        let search_id = self.search_id;
+        sender.spawn_oneshot_command(move || CommandMessage::SearchDone {
+            search_id,
+            result: youtube.search(&query, SEARCH_RESULT_COUNT),
+        });
+    }
+
+    /// Videos ticked among the search results.
+    fn selected_search_targets(&self) -> Vec<DownloadTarget> {
+        self.search_results
+            .iter()
+            .filter(|result| result.is_selected())
+            .map(|result| DownloadTarget::Video {
+                id: result.info().id.clone(),
+                url: result.info().url(),
+                title: Some(result.info().title.clone()),
+                trim: None,
+            })
+            .collect()
+    }
+
+    /// The oldest pending item among those going to the least busy drive: a drive is only given
+    /// a second download when no other drive is waiting, and items of a drive keep their order.
+    fn next_pending_index(&self) -> Option<usize> {
+        let running_on = |output_dir: &Path| {
+            self.queue
+                .iter()
+                .filter(|item| item.is_downloading() && item.output_dir() == output_dir)
+                .count()
+        };
+
+        self.queue
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_pending())
+            .min_by_key(|(_, item)| running_on(item.output_dir()))
+            .map(|(index, _)| index)
+    }
+
+    /// Overall progress of the queue, shown while something is left to download.
+    fn update_queue_progress_bar(&self, widgets: &mut ConverterWidgets) {
+        let total = self.queue.len();
+        let finished = self.queue.iter().filter(|item| item.is_finished()).count();
+        let running = self.queue.iter().filter(|item| item.is_downloading()).count();
+
+        widgets.queue_progress_bar.set_visible(finished < total);
+        if total > 0 {
+            let fraction = self.queue.iter().map(QueueItem::fraction).sum::<f64>() / total as f64;
+            widgets.queue_progress_bar.set_fraction(fraction);
+            widgets.queue_progress_bar.set_text(Some(&format!(
+                "{}/{} terminé(s), {} en cours",
+                finished, total, running
+            )));
+        }
+    }
+
+    /// Progress of the prerequisite being downloaded, shown until they are installed.
+    fn update_prerequisites_progress_bar(&self, widgets: &mut ConverterWidgets) {
+        let downloading = self.converter_state == ConverterState::PreDownloading;
+        widgets.prerequisites_progress_bar.set_visible(downloading);
+        if !downloading {
+            return;
+        }
+
+        let progress = &self.prerequisites_progress;
+        widgets.prerequisites_progress_bar.set_fraction(progress.fraction().unwrap_or_default());
+        let text = match progress.total_bytes {
+            _ if progress.name.is_empty() => String::from("Connexion"),
+            Some(total) => format!(
+                "{} — {} / {}",
+                progress.name,
+                format_size(progress.downloaded_bytes),
+                format_size(total)
+            ),
+            None => format!("{} — {}", progress.name, format_size(progress.downloaded_bytes)),
+        };
+        widgets.prerequisites_progress_bar.set_text(Some(&text));
+    }
+
     fn set_button_loading_text(&self, widgets: &mut ConverterWidgets, text: &str) {
         let hbox = gtk::Box::builder().orientation(Orientation::Horizontal).spacing(5).build();
         let label = gtk::Label::new(Some(text));
@@ -278,7 +1569,255 @@ impl Converter {
 
         widgets.save_button.set_child(Some(&hbox));
         widgets.save_button.set_sensitive(false);
-        widgets.device_combo.set_sensitive(false);
-        widgets.link_input.set_sensitive(false);
+        set_inputs_sensitive(widgets, false);
+    }
+}
+
+/// Downloads a queue item in the background, reporting its progress to the converter.
+fn start_download(job: DownloadJob, sender: ComponentSender<Converter>) {
+    let DownloadJob { id, target, mut youtube, drive_name, output_dir, reserved_space, cancel_handle } = job;
+
+    sender.spawn_command(move |out| {
+        let history_entry = |video_id: &str, title: &str| {
+            HistoryEntry::new(video_id.to_string(), title.to_string(), drive_name.clone(), output_dir.clone())
+        };
+        let downloaded_entry =
+            |file: &DownloadedFile| history_entry(&file.id, &file.title).succeeded(file.path.clone());
+        // The next items then install them again instead of failing one after the other
+        let report_missing = |e: &DownloadError| {
+            if matches!(e, DownloadError::MissingPrerequisite(_)) {
+                out.emit(CommandMessage::PrerequisiteMissing);
+            }
+        };
+
+        let message = match target {
+            DownloadTarget::Video { id: video_id, url, title, trim } => {
+                let result = youtube.download(url.clone(), &output_dir, trim).and_then(|process| {
+                    let mut process = process.with_cancel_handle(cancel_handle);
+                    if let Some(progress) = process.progress() {
+                        for progress in progress {
+                            out.emit(CommandMessage::DownloadProgress { id, progress });
+                        }
+                    }
+                    process.wait_with_progress(|progress| out.emit(CommandMessage::DownloadProgress { id, progress }))
+                });
+
+                match result {
+                    Ok(files) if files.is_empty() => CommandMessage::DownloadFinished {
+                        id,
+                        summary: String::from("Déjà présente sur la clé, ignorée"),
+                        history: Vec::new(),
+                    },
+                    Ok(files) => CommandMessage::DownloadFinished {
+                        id,
+                        summary: files_summary(&files),
+                        history: files.iter().map(downloaded_entry).collect(),
+                    },
+                    Err(DownloadError::Cancelled) => CommandMessage::DownloadCancelled { id, history: Vec::new() },
+                    Err(e) => {
+                        report_missing(&e);
+                        CommandMessage::DownloadFailed {
+                            id,
+                            reason: e.to_string(),
+                            history: vec![
+                                history_entry(&video_id, title.as_deref().unwrap_or(&url)).failed(e.to_string()),
+                            ],
+                        }
+                    }
+                }
+            }
+            DownloadTarget::Playlist(url) => match youtube
+                .fetch_playlist(&url)
+                .and_then(|playlist| {
+                    check_playlist_space(&youtube, &playlist, &output_dir, reserved_space).map(|()| playlist)
+                })
+            {
+                Ok(playlist) => {
+                    let report = youtube.download_playlist(&playlist, &output_dir, &cancel_handle, |event| {
+                        out.emit(match event {
+                            PlaylistEvent::TrackStarted { index, count, title } => {
+                                CommandMessage::TrackStarted { id, index, count, title }
+                            }
+                            PlaylistEvent::TrackProgress(progress) => {
+                                CommandMessage::DownloadProgress { id, progress }
+                            }
+                        })
+                    });
+                    report.failed.iter().for_each(|(_, e)| report_missing(e));
+                    let history = report
+                        .downloaded
+                        .iter()
+                        .map(downloaded_entry)
+                        .chain(report.failed.iter().map(|(entry, e)| {
+                            history_entry(&entry.id, entry.display_title()).failed(e.to_string())
+                        }))
+                        .collect();
+
+                    if report.cancelled {
+                        CommandMessage::DownloadCancelled { id, history }
+                    } else if !report.failed.is_empty() {
+                        CommandMessage::DownloadFailed { id, reason: playlist_failure_summary(&report), history }
+                    } else {
+                        CommandMessage::DownloadFinished {
+                            id,
+                            summary: playlist_summary(&report),
+                            history,
+                        }
+                    }
+                }
+                Err(e) => {
+                    report_missing(&e);
+                    CommandMessage::DownloadFailed { id, reason: e.to_string(), history: Vec::new() }
+                }
+            },
+        };
+
+        out.emit(message);
+    });
+}
+
+/// A playlist is only checked once listed, against the space left when its download starts
+/// and what the other items waiting for the drive will take.
+fn check_playlist_space(
+    youtube: &YoutubeDownloader,
+    playlist: &Playlist,
+    output_dir: &Path,
+    reserved_space: u64,
+) -> Result<(), DownloadError> {
+    check_space_for(reserved_space + youtube.estimated_playlist_size(playlist, output_dir), output_dir)
+}
+
+/// Whether `needed` bytes fit on the drive mounted at `mount_point`, a drive whose free space
+/// cannot be read is given the benefit of the doubt.
+fn check_space_for(needed: u64, mount_point: &Path) -> Result<(), DownloadError> {
+    match available_space(mount_point) {
+        // The estimates are rough, better refuse a little early than fill the drive
+        Some(available) if needed + needed / SPACE_MARGIN_DIVISOR > available => {
+            Err(DownloadError::NotEnoughSpace { needed, available })
+        }
+        _ => Ok(()),
+    }
+}
+
+fn set_inputs_sensitive(widgets: &ConverterWidgets, sensitive: bool) {
+    widgets.device_combo.set_sensitive(sensitive);
+    widgets.link_input.set_sensitive(sensitive);
+    widgets.search_input.set_sensitive(sensitive);
+    widgets.whole_playlist_row.set_sensitive(sensitive);
+    widgets.trim_start_input.set_sensitive(sensitive);
+    widgets.trim_end_input.set_sensitive(sensitive);
+    widgets.audio_format_row.set_sensitive(sensitive);
+    widgets.audio_quality_row.set_sensitive(sensitive);
+    widgets.embed_tags_row.set_sensitive(sensitive);
+    widgets.skip_archived_row.set_sensitive(sensitive);
+    widgets.normalize_row.set_sensitive(sensitive);
+    widgets.loudness_target_row.set_sensitive(sensitive);
+    widgets.split_chapters_row.set_sensitive(sensitive);
+    widgets.sponsorblock_row.set_sensitive(sensitive);
+    widgets.verify_writes_row.set_sensitive(sensitive);
+    widgets.output_template_input.set_sensitive(sensitive);
+}
+
+fn string_list<T: Display>(items: &[T]) -> gtk::StringList {
+    let labels: Vec<String> = items.iter().map(T::to_string).collect();
+    gtk::StringList::new(&labels.iter().map(String::as_str).collect::<Vec<&str>>())
+}
+
+/// Offers to download again the links already downloaded to `drive`.
+fn duplicates_toast(
+    drive: &Drive,
+    duplicates: &[(DownloadTarget, HistoryEntry)],
+    sender: &ComponentSender<Converter>,
+) -> adw::Toast {
+    let title = match duplicates {
+        [(_, entry)] => format!(
+            "« {} » a déjà été téléchargé sur {} le {}",
+            entry.title,
+            drive.name(),
+            format_date(entry.timestamp)
+        ),
+        _ => format!("{} vidéos ont déjà été téléchargées sur {}", duplicates.len(), drive.name()),
+    };
+
+    let toast = adw::Toast::builder()
+        .title(title)
+        .use_markup(false)
+        .button_label("Ajouter quand même")
+        .timeout(0)
+        .build();
+    toast.connect_button_clicked(clone!(
+        #[strong]
+        sender,
+        move |_| sender.input(Message::EnqueueDuplicates)
+    ));
+
+    toast
+}
+
+fn files_summary(files: &[DownloadedFile]) -> String {
+    // The tracks of a video split by chapters share a folder
+    if let [first, _, ..] = files {
+        if let Some(folder) = first.path.parent().and_then(Path::file_name) {
+            return format!("{} pistes dans {}", files.len(), folder.to_string_lossy());
+        }
+    }
+
+    files
+        .iter()
+        .filter_map(|file| file.path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn playlist_summary(report: &PlaylistReport) -> String {
+    let mut summary = format!("{} piste(s) téléchargée(s)", report.downloaded.len());
+    if report.skipped > 0 {
+        summary.push_str(&format!(", {} déjà présente(s) sur la clé", report.skipped));
+    }
+
+    summary
+}
+
+fn playlist_failure_summary(report: &PlaylistReport) -> String {
+    let mut summary = format!("{}, {} en échec :", playlist_summary(report), report.failed.len());
+    for (entry, error) in &report.failed {
+        summary.push_str(&format!("\n• {} : {}", entry.display_title(), error));
+    }
+
+    summary
+}
+
+impl Preview {
+    fn url(&self) -> Option<&str> {
+        match self {
+            Preview::Hidden => None,
+            Preview::Loading { url } | Preview::Ready { url, .. } | Preview::Failed { url, .. } => Some(url),
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["o", "Ko", "Mo", "Go"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
     }
 }
@@ -0,0 +1,80 @@
+use crate::history::{History, HistoryEntry};
+use libadwaita::glib;
+use libadwaita::prelude::ActionRowExt;
+use relm4::{adw, gtk};
+
+/// Lists the downloads of `history`, the most recent first.
+pub fn history_dialog(history: &History) -> adw::Dialog {
+    let header_bar = adw::HeaderBar::new();
+    let toolbar_view = adw::ToolbarView::new();
+    toolbar_view.add_top_bar(&header_bar);
+
+    if history.entries().is_empty() {
+        let status_page = adw::StatusPage::builder()
+            .icon_name("document-open-recent-symbolic")
+            .title("Aucun téléchargement")
+            .build();
+        toolbar_view.set_content(Some(&status_page));
+    } else {
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(["boxed-list"])
+            .margin_top(10)
+            .margin_bottom(10)
+            .margin_start(10)
+            .margin_end(10)
+            .valign(gtk::Align::Start)
+            .build();
+        for entry in history.entries().iter().rev() {
+            list.append(&history_row(entry));
+        }
+
+        let scrolled_window = gtk::ScrolledWindow::builder()
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .child(&list)
+            .build();
+        toolbar_view.set_content(Some(&scrolled_window));
+    }
+
+    adw::Dialog::builder()
+        .title("Historique")
+        .content_width(480)
+        .content_height(400)
+        .child(&toolbar_view)
+        .build()
+}
+
+/// Local date and time of a history timestamp, such as `14/03/2025 18:42`.
+pub fn format_date(timestamp: u64) -> String {
+    glib::DateTime::from_unix_local(timestamp as i64)
+        .and_then(|date| date.format("%d/%m/%Y %H:%M"))
+        .map(|date| date.to_string())
+        .unwrap_or_default()
+}
+
+fn history_row(entry: &HistoryEntry) -> adw::ActionRow {
+    let mut subtitle = format!("{} — {}", format_date(entry.timestamp), entry.drive);
+    if let Some(error) = &entry.error {
+        subtitle.push_str(&format!("\n{}", error));
+    }
+
+    let (icon, css_class) = if entry.is_success() {
+        ("emblem-ok-symbolic", "success")
+    } else {
+        ("dialog-error-symbolic", "error")
+    };
+    let status_icon = gtk::Image::builder()
+        .icon_name(icon)
+        .css_classes([css_class])
+        .build();
+
+    let row = adw::ActionRow::builder()
+        .use_markup(false)
+        .title(entry.title.as_str())
+        .subtitle(subtitle)
+        .subtitle_lines(3)
+        .build();
+    row.add_prefix(&status_icon);
+
+    row
+}
@@ -0,0 +1,344 @@
+use crate::drives::drive_mod::Drive;
+use crate::yt::progress::{DownloadPhase, DownloadProgress};
+use crate::yt::{CancelHandle, TrimRange, YoutubeDownloader};
+use super::{format_duration, format_size};
+use relm4::factory::{DynamicIndex, FactoryComponent, FactorySender};
+use relm4::gtk::glib::clone;
+use relm4::gtk::prelude::{BoxExt, ButtonExt, WidgetExt};
+use relm4::{adw, gtk};
+use libadwaita::prelude::{ActionRowExt, PreferencesRowExt};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub enum DownloadTarget {
+    /// `title` is known when the video was previewed or found by a search.
+    Video { id: String, url: String, title: Option<String>, trim: Option<TrimRange> },
+    Playlist(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueItemState {
+    Pending,
+    Downloading,
+    /// The download succeeded, with a short description of what was written.
+    Done(String),
+    Failed(String),
+}
+
+#[derive(Debug)]
+pub enum QueueItemMessage {
+    Cancel,
+    Retry,
+    Remove,
+}
+
+#[derive(Debug)]
+pub enum QueueItemOutput {
+    Retry(u64),
+    Remove(u64),
+}
+
+/// Everything a worker needs to download a queue item, detached from the UI.
+pub struct DownloadJob {
+    pub id: u64,
+    pub target: DownloadTarget,
+    pub youtube: YoutubeDownloader,
+    pub drive_name: String,
+    pub output_dir: PathBuf,
+    /// Space the other items waiting for the same drive are expected to take on it.
+    pub reserved_space: u64,
+    pub cancel_handle: CancelHandle,
+}
+
+/// A link waiting in the download queue. The downloader settings and the drive are the ones
+/// chosen when the link was added, changing them afterwards only affects the next links.
+pub struct QueueItem {
+    id: u64,
+    target: DownloadTarget,
+    youtube: YoutubeDownloader,
+    drive_name: String,
+    output_dir: PathBuf,
+    title: String,
+    estimated_size: Option<u64>,
+    state: QueueItemState,
+    track: Option<(usize, usize, String)>,
+    progress: DownloadProgress,
+    cancel_handle: Option<CancelHandle>,
+}
+
+pub struct QueueItemWidgets {
+    row: adw::ActionRow,
+    status_icon: gtk::Image,
+    progress_bar: gtk::ProgressBar,
+    cancel_button: gtk::Button,
+    retry_button: gtk::Button,
+    remove_button: gtk::Button,
+}
+
+impl QueueItem {
+    pub fn new(
+        id: u64,
+        target: DownloadTarget,
+        youtube: YoutubeDownloader,
+        drive: &Drive,
+        estimated_size: Option<u64>,
+    ) -> Self {
+        let title = match &target {
+            DownloadTarget::Video { url, title, .. } => title.clone().unwrap_or_else(|| url.clone()),
+            DownloadTarget::Playlist(url) => format!("Playlist : {}", url),
+        };
+
+        Self {
+            id,
+            target,
+            youtube,
+            drive_name: drive.name(),
+            output_dir: drive.mount_point(),
+            title,
+            estimated_size,
+            state: QueueItemState::Pending,
+            track: None,
+            progress: DownloadProgress::default(),
+            cancel_handle: None,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Approximate size of the files to write, `None` when unknown before the download.
+    pub fn estimated_size(&self) -> Option<u64> {
+        self.estimated_size
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.state == QueueItemState::Pending
+    }
+
+    pub fn is_downloading(&self) -> bool {
+        self.state == QueueItemState::Downloading
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, QueueItemState::Done(_) | QueueItemState::Failed(_))
+    }
+
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    /// Share of the item already processed, a finished item counts as complete whatever its outcome.
+    pub fn fraction(&self) -> f64 {
+        match self.state {
+            QueueItemState::Pending => 0.0,
+            QueueItemState::Downloading => {
+                let fraction = self.progress.fraction().unwrap_or_default();
+                match &self.track {
+                    Some((index, count, _)) => (*index as f64 + fraction) / *count as f64,
+                    None => fraction,
+                }
+            }
+            QueueItemState::Done(_) | QueueItemState::Failed(_) => 1.0,
+        }
+    }
+
+    /// Marks the item as downloading and hands out what the worker needs.
+    pub fn start(&mut self, reserved_space: u64) -> DownloadJob {
+        let cancel_handle = CancelHandle::default();
+        self.cancel_handle = Some(cancel_handle.clone());
+        self.state = QueueItemState::Downloading;
+        self.track = None;
+        self.progress = DownloadProgress::default();
+
+        DownloadJob {
+            id: self.id,
+            target: self.target.clone(),
+            youtube: self.youtube.clone(),
+            drive_name: self.drive_name.clone(),
+            output_dir: self.output_dir.clone(),
+            reserved_space,
+            cancel_handle,
+        }
+    }
+
+    fn cancel(&self) {
+        if let Some(cancel_handle) = &self.cancel_handle {
+            cancel_handle.cancel();
+        }
+    }
+
+    pub fn set_track(&mut self, index: usize, count: usize, title: String) {
+        self.track = Some((index, count, title));
+        self.progress = DownloadProgress::default();
+    }
+
+    pub fn set_progress(&mut self, progress: DownloadProgress) {
+        self.progress = progress;
+    }
+
+    pub fn set_state(&mut self, state: QueueItemState) {
+        if state != QueueItemState::Downloading {
+            self.cancel_handle = None;
+        }
+        self.state = state;
+    }
+
+    fn progress_text(&self) -> String {
+        let progress = &self.progress;
+        let mut text = match &self.track {
+            Some((index, count, title)) => format!("Piste {}/{} : {} — ", index + 1, count, title),
+            None => String::new(),
+        };
+        text.push_str(match progress.phase {
+            DownloadPhase::Extracting => "Analyse de la vidéo",
+            DownloadPhase::Downloading => "Téléchargement",
+            DownloadPhase::Converting => "Conversion",
+            DownloadPhase::Copying => "Copie sur la clé",
+        });
+
+        if let Some(fraction) = progress.fraction() {
+            text.push_str(&format!(" — {:.0} %", fraction * 100.0));
+        }
+        if progress.phase == DownloadPhase::Downloading {
+            if let Some(speed) = progress.speed {
+                text.push_str(&format!(" — {}/s", format_size(speed as u64)));
+            }
+            if let Some(eta) = progress.eta {
+                text.push_str(&format!(" — {} restantes", format_duration(eta)));
+            }
+        }
+
+        text
+    }
+}
+
+impl FactoryComponent for QueueItem {
+    type ParentWidget = gtk::ListBox;
+    type CommandOutput = ();
+    type Input = QueueItemMessage;
+    type Output = QueueItemOutput;
+    type Init = QueueItem;
+    type Root = adw::ActionRow;
+    type Widgets = QueueItemWidgets;
+    type Index = DynamicIndex;
+
+    fn init_model(init: Self::Init, _index: &Self::Index, _sender: FactorySender<Self>) -> Self {
+        init
+    }
+
+    fn init_root(&self) -> Self::Root {
+        adw::ActionRow::builder()
+            .use_markup(false)
+            .title_lines(1)
+            .subtitle_lines(3)
+            .build()
+    }
+
+    fn init_widgets(
+        &mut self,
+        _index: &Self::Index,
+        root: Self::Root,
+        _returned_widget: &gtk::ListBoxRow,
+        sender: FactorySender<Self>,
+    ) -> Self::Widgets {
+        let status_icon = gtk::Image::new();
+        let progress_bar = gtk::ProgressBar::builder()
+            .valign(gtk::Align::Center)
+            .width_request(80)
+            .visible(false)
+            .build();
+        let cancel_button = gtk::Button::builder()
+            .icon_name("process-stop-symbolic")
+            .tooltip_text("Annuler")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        let retry_button = gtk::Button::builder()
+            .icon_name("view-refresh-symbolic")
+            .tooltip_text("Réessayer")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        let remove_button = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Retirer de la liste")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        let buttons = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(5)
+            .build();
+        buttons.append(&progress_bar);
+        buttons.append(&cancel_button);
+        buttons.append(&retry_button);
+        buttons.append(&remove_button);
+
+        cancel_button.connect_clicked(clone!(
+            #[strong]
+            sender,
+            move |_| sender.input(QueueItemMessage::Cancel)
+        ));
+
+        retry_button.connect_clicked(clone!(
+            #[strong]
+            sender,
+            move |_| sender.input(QueueItemMessage::Retry)
+        ));
+
+        remove_button.connect_clicked(clone!(
+            #[strong]
+            sender,
+            move |_| sender.input(QueueItemMessage::Remove)
+        ));
+
+        root.add_prefix(&status_icon);
+        root.add_suffix(&buttons);
+
+        let mut widgets = QueueItemWidgets {
+            row: root,
+            status_icon,
+            progress_bar,
+            cancel_button,
+            retry_button,
+            remove_button,
+        };
+        self.update_view(&mut widgets, sender);
+
+        widgets
+    }
+
+    fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
+        match message {
+            QueueItemMessage::Cancel => self.cancel(),
+            QueueItemMessage::Retry => sender.output(QueueItemOutput::Retry(self.id)).unwrap(),
+            QueueItemMessage::Remove => sender.output(QueueItemOutput::Remove(self.id)).unwrap(),
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, _sender: FactorySender<Self>) {
+        widgets.row.set_title(&self.title);
+        widgets.progress_bar.set_visible(self.is_downloading());
+        widgets.cancel_button.set_visible(self.is_downloading());
+        widgets.retry_button.set_visible(matches!(self.state, QueueItemState::Failed(_)));
+        widgets.remove_button.set_visible(!self.is_downloading());
+
+        let (icon, css_classes, subtitle) = match &self.state {
+            QueueItemState::Pending => ("document-open-recent-symbolic", &[][..], String::from("En attente")),
+            QueueItemState::Downloading => ("folder-download-symbolic", &[][..], self.progress_text()),
+            QueueItemState::Done(summary) => ("emblem-ok-symbolic", &["success"][..], summary.clone()),
+            QueueItemState::Failed(reason) => ("dialog-error-symbolic", &["error"][..], reason.clone()),
+        };
+        widgets.status_icon.set_icon_name(Some(icon));
+        widgets.status_icon.set_css_classes(css_classes);
+        widgets.row.set_subtitle(&subtitle);
+
+        if self.is_downloading() {
+            match self.progress.fraction() {
+                Some(fraction) => widgets.progress_bar.set_fraction(fraction),
+                None => widgets.progress_bar.pulse(),
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::yt::metadata::VideoInfo;
+use super::format_duration;
+use relm4::factory::{DynamicIndex, FactoryComponent, FactorySender};
+use relm4::gtk::gdk;
+use relm4::gtk::glib::clone;
+use relm4::gtk::prelude::{CheckButtonExt, WidgetExt};
+use relm4::{adw, gtk};
+use libadwaita::prelude::{ActionRowExt, PreferencesRowExt};
+
+#[derive(Debug)]
+pub enum SearchResultMessage {
+    Toggled(bool),
+}
+
+/// A video found by a keyword search, the user ticks the ones to download.
+pub struct SearchResult {
+    info: VideoInfo,
+    thumbnail: Option<gdk::Texture>,
+    selected: bool,
+}
+
+pub struct SearchResultWidgets {
+    check_button: gtk::CheckButton,
+    picture: gtk::Picture,
+}
+
+impl SearchResult {
+    pub fn new(info: VideoInfo) -> Self {
+        Self {
+            info,
+            thumbnail: None,
+            selected: false,
+        }
+    }
+
+    pub fn info(&self) -> &VideoInfo {
+        &self.info
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.selected
+    }
+
+    pub fn deselect(&mut self) {
+        self.selected = false;
+    }
+
+    pub fn set_thumbnail(&mut self, thumbnail: gdk::Texture) {
+        self.thumbnail = Some(thumbnail);
+    }
+
+    fn subtitle(&self) -> String {
+        let channel = self.info.channel().map(str::to_string);
+        let duration = self.info.duration().map(format_duration);
+
+        channel.into_iter().chain(duration).collect::<Vec<_>>().join(" — ")
+    }
+}
+
+impl FactoryComponent for SearchResult {
+    type ParentWidget = gtk::ListBox;
+    type CommandOutput = ();
+    type Input = SearchResultMessage;
+    type Output = ();
+    type Init = SearchResult;
+    type Root = adw::ActionRow;
+    type Widgets = SearchResultWidgets;
+    type Index = DynamicIndex;
+
+    fn init_model(init: Self::Init, _index: &Self::Index, _sender: FactorySender<Self>) -> Self {
+        init
+    }
+
+    fn init_root(&self) -> Self::Root {
+        adw::ActionRow::builder()
+            .use_markup(false)
+            .title_lines(2)
+            .build()
+    }
+
+    fn init_widgets(
+        &mut self,
+        _index: &Self::Index,
+        root: Self::Root,
+        _returned_widget: &gtk::ListBoxRow,
+        sender: FactorySender<Self>,
+    ) -> Self::Widgets {
+        let check_button = gtk::CheckButton::builder()
+            .valign(gtk::Align::Center)
+            .build();
+        let picture = gtk::Picture::builder()
+            .content_fit(gtk::ContentFit::Cover)
+            .width_request(96)
+            .height_request(54)
+            .margin_top(5)
+            .margin_bottom(5)
+            .build();
+
+        check_button.connect_toggled(clone!(
+            #[strong]
+            sender,
+            move |e| sender.input(SearchResultMessage::Toggled(e.is_active()))
+        ));
+
+        root.set_title(&self.info.title);
+        root.set_subtitle(&self.subtitle());
+        root.add_prefix(&check_button);
+        root.add_prefix(&picture);
+        root.set_activatable_widget(Some(&check_button));
+
+        let mut widgets = SearchResultWidgets {
+            check_button,
+            picture,
+        };
+        self.update_view(&mut widgets, sender);
+
+        widgets
+    }
+
+    fn update(&mut self, message: Self::Input, _sender: FactorySender<Self>) {
+        match message {
+            SearchResultMessage::Toggled(selected) => self.selected = selected,
+        }
+    }
+
+    fn update_view(&self, widgets: &mut Self::Widgets, _sender: FactorySender<Self>) {
+        widgets.check_button.set_active(self.selected);
+        widgets.picture.set_paintable(self.thumbnail.as_ref());
+    }
+}
@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_HISTORY_FILE: &str = "history.jsonl";
+
+/// A video downloaded, or that failed to download, to a drive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub video_id: String,
+    pub title: String,
+    /// Name of the drive, as shown in the drive list.
+    pub drive: String,
+    pub drive_path: PathBuf,
+    pub file: Option<PathBuf>,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// `None` when the download succeeded.
+    pub error: Option<String>,
+}
+
+/// Every download made with the application, stored one JSON entry per line
+/// so that recording a download never rewrites the whole file.
+pub struct History {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+}
+
+impl HistoryEntry {
+    pub fn new(video_id: String, title: String, drive: String, drive_path: PathBuf) -> Self {
+        Self {
+            video_id,
+            title,
+            drive,
+            drive_path,
+            file: None,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            error: None,
+        }
+    }
+
+    pub fn succeeded(mut self, file: PathBuf) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    pub fn failed(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl History {
+    /// Reads the history stored at `path`. A missing file is an empty history,
+    /// and a line that cannot be read, such as one cut by a crash, is skipped.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = std::fs::read_to_string(&path)
+            .map(|history| {
+                history
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { path, entries }
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// Appends `entries` to the history file.
+    pub fn add(&mut self, entries: Vec<HistoryEntry>) -> std::io::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = String::new();
+        for entry in &entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(lines.as_bytes())?;
+        self.entries.extend(entries);
+
+        Ok(())
+    }
+
+    /// The last successful download of `video_id` to the drive `drive` mounted at `drive_path`.
+    pub fn find(&self, video_id: &str, drive: &str, drive_path: &Path) -> Option<&HistoryEntry> {
+        self.entries.iter().rev().find(|entry| {
+            entry.is_success()
+                && entry.video_id == video_id
+                && entry.drive == drive
+                && entry.drive_path == drive_path
+        })
+    }
+}
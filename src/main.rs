@@ -4,6 +4,7 @@ use relm4::RelmApp;
 
 mod drives;
 mod gui;
+mod history;
 pub mod yt;
 
 pub fn main() {
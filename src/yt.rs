@@ -1,27 +1,63 @@
-use std::fs::File;
+mod archive;
+pub mod chapters;
+mod error;
+pub mod filename;
+pub mod loudness;
+pub mod metadata;
+pub mod options;
+pub mod playlist;
+pub mod prerequisites;
+mod process;
+pub mod progress;
+pub mod sponsorblock;
+mod staging;
+pub mod trim;
+pub mod url;
+pub mod verify;
+
+pub use archive::{ArchiveCopy, DownloadArchive};
+pub use chapters::ChapterSplit;
+pub use error::DownloadError;
+pub use options::{AudioFormat, AudioQuality};
+pub use loudness::Normalization;
+pub use prerequisites::{InstallProgress, Manifest};
+pub use process::{CancelHandle, DownloadProcess, DownloadedFile};
+pub use trim::TrimRange;
+pub use verify::Verification;
+
+use sponsorblock::SponsorBlockCategory;
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
-#[cfg(target_os = "windows")]
-use sevenz_rust::{Password, SevenZReader};
-
-#[cfg(target_os = "linux")]
-use tar::Archive;
-#[cfg(target_os = "linux")]
-use xz2::read::XzDecoder;
-#[cfg(target_os = "linux")]
-use std::os::unix::fs::PermissionsExt;
+#[cfg(not(target_os = "windows"))]
+use std::os::unix::process::CommandExt;
 
 #[cfg(target_os = "windows")]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 pub const DEFAULT_LIB_DIR: &str = "lib";
+const WORK_DIR_NAME: &str = "convertisseur";
+/// Crops the centered square of the thumbnail, as expected by car stereos and players.
+const SQUARE_COVER_FILTER: &str = "crop='if(gt(ih,iw),iw,ih)':'if(gt(iw,ih),ih,iw)'";
 
 #[derive(Clone, Debug)]
 pub struct YoutubeDownloader {
+    libs_folder: PathBuf,
     yt_dlp_path: PathBuf,
     ffmpeg_path: PathBuf,
+    audio_format: AudioFormat,
+    audio_quality: AudioQuality,
+    embed_metadata: bool,
+    embed_cover_art: bool,
+    output_template: String,
+    use_download_archive: bool,
+    loudness_target: Option<f64>,
+    split_chapters: bool,
+    sponsorblock_remove: Vec<SponsorBlockCategory>,
+    sponsorblock_api: Option<String>,
+    verify_writes: bool,
 }
 
 impl YoutubeDownloader {
@@ -30,6 +66,18 @@ impl YoutubeDownloader {
         Self {
             yt_dlp_path: libs_folder.join("yt-dlp.exe"),
             ffmpeg_path: libs_folder.join("ffmpeg.exe"),
+            audio_format: AudioFormat::default(),
+            audio_quality: AudioQuality::default(),
+            embed_metadata: true,
+            embed_cover_art: true,
+            output_template: String::from(filename::DEFAULT_OUTPUT_TEMPLATE),
+            use_download_archive: true,
+            loudness_target: None,
+            split_chapters: false,
+            sponsorblock_remove: Vec::new(),
+            sponsorblock_api: None,
+            verify_writes: false,
+            libs_folder,
         }
     }
     
@@ -38,124 +86,384 @@ impl YoutubeDownloader {
         Self {
             yt_dlp_path: libs_folder.join("yt-dlp"),
             ffmpeg_path: libs_folder.join("ffmpeg"),
+            audio_format: AudioFormat::default(),
+            audio_quality: AudioQuality::default(),
+            embed_metadata: true,
+            embed_cover_art: true,
+            output_template: String::from(filename::DEFAULT_OUTPUT_TEMPLATE),
+            use_download_archive: true,
+            loudness_target: None,
+            split_chapters: false,
+            sponsorblock_remove: Vec::new(),
+            sponsorblock_api: None,
+            verify_writes: false,
+            libs_folder,
         }
     }
 
-    pub fn check_prerequisites(&self) -> bool {
-        self.yt_dlp_path.exists() && self.ffmpeg_path.exists()
+    pub fn set_audio_format(&mut self, audio_format: AudioFormat) {
+        self.audio_format = audio_format;
     }
-    
-    #[cfg(target_os = "linux")]
-    pub fn check_update() -> Child {
-        Command::new(PathBuf::from(DEFAULT_LIB_DIR).join("yt-dlp"))
-            .arg("-U")
-            .spawn()
-            .unwrap()
+
+    pub fn set_audio_quality(&mut self, audio_quality: AudioQuality) {
+        self.audio_quality = audio_quality;
     }
 
-    #[cfg(target_os = "windows")]
-    pub fn check_update() -> Child {
-        Command::new(PathBuf::from(DEFAULT_LIB_DIR).join("yt-dlp.exe"))
-            .arg("-U")
-            .creation_flags(CREATE_NO_WINDOW)
-            .spawn()
-            .unwrap()
+    /// Writes the title, artist and album of the video in the tags of the audio file.
+    pub fn set_embed_metadata(&mut self, embed_metadata: bool) {
+        self.embed_metadata = embed_metadata;
     }
 
-    #[cfg(target_os = "linux")]
-    pub async fn download_prerequisites(libs_folder: PathBuf) {
-        std::fs::create_dir_all(libs_folder.clone()).unwrap();
-        let resp = reqwest::get("https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp").await.unwrap().bytes().await.unwrap();
-        std::fs::write(libs_folder.join("yt-dlp"), &resp).unwrap();
-        let resp = reqwest::get("https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-amd64-static.tar.xz").await.unwrap().bytes().await.unwrap();
-        std::fs::write(libs_folder.join("ffmpeg.tar.xz"), &resp).unwrap();
-
-        let file = File::open(libs_folder.join("ffmpeg.tar.xz")).unwrap();
-        let decoder = XzDecoder::new(file);
-        let mut archive = Archive::new(decoder);
-        for entry in archive.entries().unwrap() {
-            let mut entry = entry.unwrap();
-            if entry.path().unwrap().to_string_lossy().contains("ffmpeg") {
-                let mut output_file = File::create(libs_folder.join("ffmpeg")).unwrap();
-                std::io::copy(&mut entry, &mut output_file).unwrap();
+    /// Embeds the video thumbnail, cropped to a square, as the cover art of the audio file.
+    pub fn set_embed_cover_art(&mut self, embed_cover_art: bool) {
+        self.embed_cover_art = embed_cover_art;
+    }
+
+    /// yt-dlp output template the files are named after, such as `%(artist)s - %(title)s`.
+    /// The rendered names are sanitized so that they can be written on any USB drive.
+    pub fn set_output_template(&mut self, output_template: String) {
+        self.output_template = output_template;
+    }
+
+    /// Records the downloaded videos in an archive on the drive and skips those already in it.
+    pub fn set_use_download_archive(&mut self, use_download_archive: bool) {
+        self.use_download_archive = use_download_archive;
+    }
+
+    /// Normalises the loudness of the downloaded files to `loudness_target` LUFS, before they
+    /// are written on the drive. `None` keeps the volume of the video.
+    pub fn set_loudness_target(&mut self, loudness_target: Option<f64>) {
+        self.loudness_target = loudness_target;
+    }
+
+    /// Cuts the videos with chapters into one track per chapter, written in a folder named
+    /// after the video. A trimmed video is never split.
+    pub fn set_split_chapters(&mut self, split_chapters: bool) {
+        self.split_chapters = split_chapters;
+    }
+
+    /// Cuts the segments of these SponsorBlock categories out of the audio, none are by default.
+    pub fn set_sponsorblock_remove(&mut self, categories: Vec<SponsorBlockCategory>) {
+        self.sponsorblock_remove = categories;
+    }
+
+    /// SponsorBlock server the segments are asked to, `None` for the public one.
+    /// The address is given as is to yt-dlp's `--sponsorblock-api`.
+    pub fn set_sponsorblock_api(&mut self, api_url: Option<String>) {
+        self.sponsorblock_api = api_url;
+    }
+
+    /// Reads back each file written on the drive, compares it with the downloaded one and decodes
+    /// it with ffmpeg. A file failing the verification fails its download.
+    pub fn set_verify_writes(&mut self, verify_writes: bool) {
+        self.verify_writes = verify_writes;
+    }
+
+    /// Approximate size of the file written for a video lasting `duration`.
+    pub fn estimated_size(&self, duration: std::time::Duration) -> u64 {
+        let bitrate = self.audio_format.estimated_bitrate(self.audio_quality) as f64 * 1000.0;
+        (duration.as_secs_f64() * bitrate / 8.0) as u64
+    }
+
+    /// Whether yt-dlp and ffmpeg are installed, in the versions pinned in the bundled manifest.
+    pub fn check_prerequisites(&self) -> bool {
+        self.yt_dlp_path.exists()
+            && self.ffmpeg_path.exists()
+            && Manifest::bundled()
+                .current_platform()
+                .is_ok_and(|prerequisites| prerequisites.is_installed(&self.libs_folder))
+    }
+
+    fn require_prerequisites(&self) -> Result<(), DownloadError> {
+        for path in [&self.yt_dlp_path, &self.ffmpeg_path] {
+            if !path.exists() {
+                return Err(DownloadError::MissingPrerequisite(path.clone()));
             }
         }
 
-        std::fs::set_permissions(libs_folder.join("ffmpeg"), std::fs::Permissions::from_mode(0o744)).unwrap();
-        std::fs::set_permissions(libs_folder.join("yt-dlp"), std::fs::Permissions::from_mode(0o744)).unwrap();
-        std::fs::remove_file(libs_folder.join("ffmpeg.tar.xz")).unwrap();
+        Ok(())
     }
 
-    #[cfg(target_os = "windows")]
-    pub async fn download_prerequisites(libs_folder: PathBuf) {
-        std::fs::create_dir_all(libs_folder.clone()).unwrap();
-        let resp = reqwest::get("https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp.exe").await.unwrap().bytes().await.unwrap();
-        std::fs::write(libs_folder.join("yt-dlp.exe"), &resp).unwrap();
-        let resp = reqwest::get("https://www.gyan.dev/ffmpeg/builds/ffmpeg-git-essentials.7z").await.unwrap().bytes().await.unwrap();
-        std::fs::write(libs_folder.join("ffmpeg.7z"), &resp).unwrap();
-
-        let mut archive = SevenZReader::open(libs_folder.join("ffmpeg.7z"), Password::empty()).unwrap();
-        archive.for_each_entries( |entry, r| {
-            if entry.name().contains("ffmpeg.exe") {
-                let mut output_file = File::create(libs_folder.join("ffmpeg.exe")).unwrap();
-                std::io::copy(r, &mut output_file).unwrap();
-                Ok(true)
-            } else {
-                Ok(true)
+    /// Installs the versions of yt-dlp and ffmpeg pinned in the bundled manifest into `libs_folder`,
+    /// reporting the progress of each download to `on_progress`.
+    pub async fn download_prerequisites(
+        libs_folder: PathBuf,
+        on_progress: impl FnMut(InstallProgress),
+    ) -> Result<(), DownloadError> {
+        let manifest = Manifest::bundled();
+        prerequisites::install(&libs_folder, manifest.current_platform()?, on_progress).await
+    }
+
+    /// Starts yt-dlp, follow the download with [`DownloadProcess::progress`]. A video found in the
+    /// download archive of the drive is skipped, the process then finishes without any file.
+    /// Only the `trim` range of the video is downloaded when one is given.
+    pub fn download(
+        &mut self,
+        url: String,
+        out_path: &Path,
+        trim: Option<TrimRange>,
+    ) -> Result<DownloadProcess, DownloadError> {
+        self.require_prerequisites()?;
+
+        // Left by a copy interrupted when the drive was unplugged
+        staging::remove_partial_files(out_path);
+        let work_dir = new_work_dir()?;
+        let archive = if self.use_download_archive {
+            Some(DownloadArchive::copy_to(out_path, &work_dir)?)
+        } else {
+            None
+        };
+        let split_chapters = self.split_chapters && trim.is_none();
+
+        let mut command = new_command(&self.yt_dlp_path);
+        #[cfg(not(target_os = "windows"))]
+        command.process_group(0);
+        command.args(self.download_args(&url, &work_dir, trim, archive.as_ref().map(ArchiveCopy::path)));
+
+        let child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let mut process = DownloadProcess::new(child)
+            .with_output_dir(out_path.to_path_buf())
+            .with_work_dir(work_dir);
+        if let Some(archive) = archive {
+            process = process.with_archive(archive);
+        }
+        if split_chapters {
+            process = process.with_chapter_split(ChapterSplit {
+                ffmpeg_path: self.ffmpeg_path.clone(),
+            });
+        }
+        if self.verify_writes {
+            process = process.with_verification(Verification {
+                ffmpeg_path: self.ffmpeg_path.clone(),
+            });
+        }
+        Ok(match self.loudness_target {
+            Some(target_lufs) => process.with_normalization(Normalization {
+                ffmpeg_path: self.ffmpeg_path.clone(),
+                target_lufs,
+                audio_format: self.audio_format,
+                audio_quality: self.audio_quality,
+            }),
+            None => process,
+        })
+    }
+
+    /// Arguments making yt-dlp download `url` into `work_dir` with the current settings,
+    /// recording it in the `archive` copy when one is given.
+    fn download_args(
+        &self,
+        url: &str,
+        work_dir: &Path,
+        trim: Option<TrimRange>,
+        archive: Option<&Path>,
+    ) -> Vec<String> {
+        // The files are written under the video identifier, a name always valid on the drive,
+        // and renamed once finished after the output template
+        let [video_template, filepath_template, chapters_template, filename_template] =
+            progress::file_templates(&self.output_template);
+
+        let mut args: Vec<String> = [
+            url,
+            "-o",
+            "%(id)s.%(ext)s",
+            "-x",
+            "-q",
+            "--no-simulate",
+            "--print",
+            video_template.as_str(),
+            "--print",
+            filepath_template.as_str(),
+            "--print",
+            chapters_template.as_str(),
+            "--print",
+            filename_template.as_str(),
+            "--audio-format",
+            self.audio_format.as_arg(),
+            "--no-playlist",
+            "--ffmpeg-location",
+            self.ffmpeg_path.display().to_string().as_str(),
+            "-P",
+            work_dir.display().to_string().as_str(),
+            "--progress",
+            "--newline",
+            "--progress-template",
+            progress::DOWNLOAD_TEMPLATE,
+            "--progress-template",
+            progress::POSTPROCESS_TEMPLATE,
+        ]
+        .map(String::from)
+        .to_vec();
+        let mut push = |values: &[&str]| args.extend(values.iter().map(|value| value.to_string()));
+        if !self.audio_format.is_lossless() {
+            push(&["--audio-quality", self.audio_quality.as_arg().as_str()]);
+        }
+        if let Some(trim) = trim {
+            // Cutting on keyframes only would start a few seconds early
+            push(&["--download-sections", trim.as_section_arg().as_str(), "--force-keyframes-at-cuts"]);
+        }
+        if !self.sponsorblock_remove.is_empty() {
+            let categories: Vec<&str> = self.sponsorblock_remove.iter().map(SponsorBlockCategory::as_arg).collect();
+            push(&["--sponsorblock-remove", categories.join(",").as_str()]);
+            if let Some(api_url) = &self.sponsorblock_api {
+                push(&["--sponsorblock-api", api_url.as_str()]);
             }
-        }).unwrap();
+        }
+        if self.embed_metadata {
+            push(&["--embed-metadata"]);
+        }
+        if let Some(archive) = archive {
+            push(&["--download-archive", archive.display().to_string().as_str()]);
+        }
+        if self.embed_cover_art && self.audio_format.supports_cover_art() {
+            push(&["--embed-thumbnail", "--convert-thumbnails", "jpg", "--ppa", square_cover_args().as_str()]);
+        }
 
-        std::fs::remove_file(libs_folder.join("ffmpeg.7z")).unwrap();
+        args
     }
+}
 
-    #[cfg(target_os = "windows")]
-    pub fn download(&mut self, url: String, out_path: &Path) -> Child {
-        let child = Command::new(self.yt_dlp_path.clone())
-            .args(vec![
-                url.as_str(),
-                "-o",
-                "%(title)s",
-                "-x",
-                "-q",
-                "--audio-format",
-                "mp3",
-                "--no-playlist",
-                "--ffmpeg-location",
-                self.ffmpeg_path.display().to_string().as_str(),
-                "-P",
-                out_path.display().to_string().as_str(),
-                "--progress",
-                "--progress-template",
-                "%(progress._percent_str)s"
-            ])
-            .creation_flags(CREATE_NO_WINDOW)
-            .spawn()
-            .unwrap();
-
-        child
-    }
-
-    #[cfg(not(target_os = "windows"))]
-    pub fn download(&mut self, url: String, out_path: &Path) -> Child {
-        Command::new(self.yt_dlp_path.clone())
-            .args(vec![
-                url.as_str(),
-                "-o",
-                "%(title)s",
-                "-x",
-                "-q",
-                "--audio-format",
-                "mp3",
-                "--no-playlist",
-                "--ffmpeg-location",
-                self.ffmpeg_path.display().to_string().as_str(),
-                "-P",
-                out_path.display().to_string().as_str(),
-                "--progress",
-                "--progress-template",
-                "%(progress._percent_str)s"
-            ])
-            .spawn()
-            .unwrap()
+/// Creates a local directory where yt-dlp downloads and converts a video. Only the finished file
+/// is moved to the drive, so that parallel downloads do not fight over a slow USB drive and
+/// a cancelled download leaves nothing behind on it.
+fn new_work_dir() -> std::io::Result<PathBuf> {
+    static NEXT_WORK_DIR: AtomicU64 = AtomicU64::new(0);
+
+    let work_dir = std::env::temp_dir().join(WORK_DIR_NAME).join(format!(
+        "{}-{}",
+        std::process::id(),
+        NEXT_WORK_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&work_dir)?;
+
+    Ok(work_dir)
+}
+
+#[cfg(target_os = "windows")]
+fn new_command(program: &Path) -> Command {
+    let mut command = Command::new(program);
+    command.creation_flags(CREATE_NO_WINDOW);
+    command
+}
+
+#[cfg(not(target_os = "windows"))]
+fn new_command(program: &Path) -> Command {
+    Command::new(program)
+}
+
+/// The last line ffmpeg printed before failing, the one giving the cause of the failure.
+fn ffmpeg_error_line(stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    stderr
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Makes yt-dlp convert the thumbnail to JPEG and crop it with [`SQUARE_COVER_FILTER`].
+fn square_cover_args() -> String {
+    format!(
+        "ThumbnailsConvertor+ffmpeg_o:-c:v mjpeg -qmin 1 -qscale:v 1 -vf \"{}\"",
+        SQUARE_COVER_FILTER
+    )
+}
+
+/// An empty directory of its own for a test, under the system temporary directory.
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir()
+        .join(format!("{}-test-{}", WORK_DIR_NAME, std::process::id()))
+        .join(name);
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn download_args(youtube: &YoutubeDownloader) -> Vec<String> {
+        youtube.download_args("https://www.youtube.com/watch?v=abc", Path::new("work"), None, None)
+    }
+
+    /// Values given right after `option`, once per occurrence.
+    fn values<'a>(args: &'a [String], option: &str) -> Vec<&'a str> {
+        args.windows(2)
+            .filter(|pair| pair[0] == option)
+            .map(|pair| pair[1].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn embeds_tags_and_square_cover() {
+        let mut youtube = YoutubeDownloader::new(PathBuf::from(DEFAULT_LIB_DIR));
+        youtube.set_embed_metadata(true);
+        youtube.set_embed_cover_art(true);
+        let args = download_args(&youtube);
+
+        assert!(args.iter().any(|arg| arg == "--embed-metadata"), "{:?}", args);
+        assert!(args.iter().any(|arg| arg == "--embed-thumbnail"), "{:?}", args);
+        assert_eq!(values(&args, "--convert-thumbnails"), ["jpg"]);
+        assert_eq!(values(&args, "--ppa"), [square_cover_args()]);
+        assert!(square_cover_args().starts_with("ThumbnailsConvertor+ffmpeg_o:"));
+        assert!(square_cover_args().contains(SQUARE_COVER_FILTER));
+    }
+
+    #[test]
+    fn leaves_out_disabled_tags_and_cover() {
+        let mut youtube = YoutubeDownloader::new(PathBuf::from(DEFAULT_LIB_DIR));
+        youtube.set_embed_metadata(false);
+        youtube.set_embed_cover_art(false);
+        let args = download_args(&youtube);
+
+        for option in ["--embed-metadata", "--embed-thumbnail", "--convert-thumbnails", "--ppa"] {
+            assert!(!args.iter().any(|arg| arg == option), "{} in {:?}", option, args);
+        }
+    }
+
+    #[test]
+    fn wav_files_get_no_cover() {
+        let mut youtube = YoutubeDownloader::new(PathBuf::from(DEFAULT_LIB_DIR));
+        youtube.set_embed_metadata(true);
+        youtube.set_embed_cover_art(true);
+        youtube.set_audio_format(AudioFormat::Wav);
+        let args = download_args(&youtube);
+
+        assert!(args.iter().any(|arg| arg == "--embed-metadata"), "{:?}", args);
+        assert!(!args.iter().any(|arg| arg == "--embed-thumbnail"), "{:?}", args);
+        assert!(values(&args, "--ppa").is_empty(), "{:?}", args);
+    }
+
+    #[test]
+    fn removes_sponsorblock_segments() {
+        let mut youtube = YoutubeDownloader::new(PathBuf::from(DEFAULT_LIB_DIR));
+        youtube.set_sponsorblock_remove(vec![SponsorBlockCategory::MusicOfftopic, SponsorBlockCategory::Sponsor]);
+        youtube.set_sponsorblock_api(Some(String::from("http://127.0.0.1:8080")));
+        let args = download_args(&youtube);
+
+        assert_eq!(values(&args, "--sponsorblock-remove"), ["music_offtopic,sponsor"]);
+        assert_eq!(values(&args, "--sponsorblock-api"), ["http://127.0.0.1:8080"]);
+    }
+
+    #[test]
+    fn asks_sponsorblock_only_with_categories() {
+        let mut youtube = YoutubeDownloader::new(PathBuf::from(DEFAULT_LIB_DIR));
+        youtube.set_sponsorblock_api(Some(String::from("http://127.0.0.1:8080")));
+        let args = download_args(&youtube);
+        assert!(values(&args, "--sponsorblock-remove").is_empty(), "{:?}", args);
+        assert!(values(&args, "--sponsorblock-api").is_empty(), "{:?}", args);
+
+        youtube.set_sponsorblock_remove(vec![SponsorBlockCategory::Intro]);
+        youtube.set_sponsorblock_api(None);
+        let args = download_args(&youtube);
+        assert_eq!(values(&args, "--sponsorblock-remove"), ["intro"]);
+        assert!(values(&args, "--sponsorblock-api").is_empty(), "{:?}", args);
     }
 }
@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Name of the yt-dlp download archive kept at the root of each drive.
+pub const ARCHIVE_FILE_NAME: &str = "convertisseur-archive.txt";
+const YOUTUBE_EXTRACTOR: &str = "youtube";
+
+/// Videos already written to a drive, as recorded by yt-dlp's `--download-archive`.
+/// The archive travels with the drive, so any computer can skip what it already holds.
+pub struct DownloadArchive {
+    video_ids: HashSet<String>,
+}
+
+impl DownloadArchive {
+    pub fn path(drive_path: &Path) -> PathBuf {
+        drive_path.join(ARCHIVE_FILE_NAME)
+    }
+
+    /// Reads the archive of the drive mounted at `drive_path`, a drive without archive is empty.
+    pub fn open(drive_path: &Path) -> Self {
+        let video_ids = std::fs::read_to_string(Self::path(drive_path))
+            .map(|archive| {
+                archive
+                    .lines()
+                    .filter_map(|line| line.trim().split_once(' '))
+                    .filter(|(extractor, _)| *extractor == YOUTUBE_EXTRACTOR)
+                    .map(|(_, id)| id.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { video_ids }
+    }
+
+    pub fn contains(&self, video_id: &str) -> bool {
+        self.video_ids.contains(video_id)
+    }
+
+    /// Copies the archive of the drive mounted at `drive_path` into `work_dir`, for yt-dlp to
+    /// record the download there instead of on the drive.
+    pub fn copy_to(drive_path: &Path, work_dir: &Path) -> io::Result<ArchiveCopy> {
+        let mut archive = match std::fs::read_to_string(Self::path(drive_path)) {
+            Ok(archive) => archive,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        // yt-dlp appends its lines without checking how the file ends
+        if !archive.is_empty() && !archive.ends_with('\n') {
+            archive.push('\n');
+        }
+
+        let path = work_dir.join(ARCHIVE_FILE_NAME);
+        std::fs::write(&path, &archive)?;
+
+        Ok(ArchiveCopy {
+            drive_path: drive_path.to_path_buf(),
+            path,
+            copied_len: archive.len(),
+        })
+    }
+}
+
+/// The archive of a drive copied into a work directory. yt-dlp records a video as soon as it is
+/// converted, the record only reaches the drive with [`ArchiveCopy::merge`] once the files are
+/// there, so that a failed or cancelled download is not skipped the next time.
+pub struct ArchiveCopy {
+    drive_path: PathBuf,
+    path: PathBuf,
+    copied_len: usize,
+}
+
+impl ArchiveCopy {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds the videos recorded in the copy to the archive of the drive. Downloads finishing
+    /// together merge one after the other, none of them loses the lines of the others.
+    pub fn merge(&self) -> io::Result<()> {
+        static MERGING: Mutex<()> = Mutex::new(());
+
+        let copy = std::fs::read_to_string(&self.path)?;
+        let recorded = copy.get(self.copied_len..).unwrap_or_default();
+
+        let _merging = MERGING.lock().unwrap();
+        let archive_path = DownloadArchive::path(&self.drive_path);
+        let archive = match std::fs::read_to_string(&archive_path) {
+            Ok(archive) => archive,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let known: HashSet<&str> = archive.lines().map(str::trim).collect();
+        let mut added: String = recorded
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !known.contains(line))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        if added.is_empty() {
+            return Ok(());
+        }
+        if !archive.is_empty() && !archive.ends_with('\n') {
+            added.insert(0, '\n');
+        }
+
+        let mut file = File::options().create(true).append(true).open(&archive_path)?;
+        file.write_all(added.as_bytes())?;
+        file.sync_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yt::test_dir;
+
+    #[test]
+    fn merges_the_videos_recorded_in_the_copy() {
+        let directory = test_dir("archive-merge");
+        let drive = directory.join("drive");
+        let work_dir = directory.join("work");
+        std::fs::create_dir_all(&drive).unwrap();
+        std::fs::create_dir_all(&work_dir).unwrap();
+        std::fs::write(DownloadArchive::path(&drive), "youtube aaaaaaaaaaa").unwrap();
+
+        let first = DownloadArchive::copy_to(&drive, &work_dir).unwrap();
+        let mut copy = File::options().append(true).open(first.path()).unwrap();
+        copy.write_all(b"youtube bbbbbbbbbbb\n").unwrap();
+        first.merge().unwrap();
+
+        let archive = DownloadArchive::open(&drive);
+        assert!(archive.contains("aaaaaaaaaaa"));
+        assert!(archive.contains("bbbbbbbbbbb"));
+        assert_eq!(
+            std::fs::read_to_string(DownloadArchive::path(&drive)).unwrap(),
+            "youtube aaaaaaaaaaa\nyoutube bbbbbbbbbbb\n"
+        );
+    }
+
+    #[test]
+    fn keeps_the_videos_merged_by_other_downloads() {
+        let directory = test_dir("archive-parallel");
+        let drive = directory.join("drive");
+        std::fs::create_dir_all(&drive).unwrap();
+        let copies: Vec<ArchiveCopy> = ["first", "second"]
+            .into_iter()
+            .map(|name| {
+                let work_dir = directory.join(name);
+                std::fs::create_dir_all(&work_dir).unwrap();
+                DownloadArchive::copy_to(&drive, &work_dir).unwrap()
+            })
+            .collect();
+
+        std::fs::write(copies[0].path(), "youtube aaaaaaaaaaa\n").unwrap();
+        std::fs::write(copies[1].path(), "youtube bbbbbbbbbbb\n").unwrap();
+        copies[1].merge().unwrap();
+        copies[0].merge().unwrap();
+        copies[0].merge().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(DownloadArchive::path(&drive)).unwrap(),
+            "youtube bbbbbbbbbbb\nyoutube aaaaaaaaaaa\n"
+        );
+    }
+}
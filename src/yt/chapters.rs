@@ -0,0 +1,85 @@
+use crate::yt::{CancelHandle, DownloadError, ffmpeg_error_line, new_command};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+const CHAPTERS_DIR_NAME: &str = "chapters";
+
+/// A chapter of a video, as listed by yt-dlp's `chapters` field. Times are in seconds.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Chapter {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub title: Option<String>,
+}
+
+/// Cuts an audio file into one track per chapter with ffmpeg. The tracks are copied without
+/// being encoded again, and tagged with their number and title, the video becoming the album.
+#[derive(Debug, Clone)]
+pub struct ChapterSplit {
+    pub ffmpeg_path: PathBuf,
+}
+
+impl Chapter {
+    /// The chapter title, or its number when the video does not give one.
+    pub fn display_title(&self, number: usize) -> String {
+        match self.title.as_deref().map(str::trim) {
+            Some(title) if !title.is_empty() => title.to_string(),
+            _ => format!("Piste {}", number),
+        }
+    }
+}
+
+impl ChapterSplit {
+    /// Writes the tracks of the file at `path` next to it, in a folder of their own. Returns
+    /// each track with its number and title, such as `03 - Title`, to name it after.
+    pub fn apply(
+        &self,
+        path: &Path,
+        chapters: &[Chapter],
+        album: &str,
+        cancel_handle: &CancelHandle,
+    ) -> Result<Vec<(PathBuf, String)>, DownloadError> {
+        let directory = path.parent().unwrap_or(Path::new("")).join(CHAPTERS_DIR_NAME);
+        std::fs::create_dir_all(&directory)?;
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // Keeps the tracks in order whatever their number of digits
+        let width = chapters.len().to_string().len().max(2);
+
+        let mut tracks = Vec::with_capacity(chapters.len());
+        for (index, chapter) in chapters.iter().enumerate() {
+            let number = index + 1;
+            let title = chapter.display_title(number);
+            let track = directory.join(format!("{:0width$}.{}", number, extension, width = width));
+
+            let output = cancel_handle.output(
+                new_command(&self.ffmpeg_path)
+                    .args(["-hide_banner", "-nostdin", "-nostats", "-y", "-i"])
+                    .arg(path)
+                    .args(["-ss", chapter.start_time.to_string().as_str()])
+                    .args(["-to", chapter.end_time.to_string().as_str()])
+                    .args(["-map", "0:a", "-map", "0:v?", "-c", "copy", "-map_chapters", "-1"])
+                    .arg("-metadata")
+                    .arg(format!("title={}", title))
+                    .arg("-metadata")
+                    .arg(format!("track={}/{}", number, chapters.len()))
+                    .arg("-metadata")
+                    .arg(format!("album={}", album))
+                    .arg(&track),
+            )?;
+            if !output.status.success() {
+                return Err(DownloadError::Conversion(format!(
+                    "découpage du chapitre « {} » impossible : {}",
+                    title,
+                    ffmpeg_error_line(&output.stderr)
+                )));
+            }
+
+            tracks.push((track, format!("{:0width$} - {}", number, title, width = width)));
+        }
+
+        Ok(tracks)
+    }
+}
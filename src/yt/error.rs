@@ -0,0 +1,92 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+const MEGABYTE: u64 = 1024 * 1024;
+
+#[derive(Debug)]
+pub enum DownloadError {
+    /// The request to a remote server failed.
+    Network(reqwest::Error),
+    /// A prerequisite does not have the checksum listed in the manifest, digests are in hexadecimal.
+    ChecksumMismatch { url: String, expected: String, actual: String },
+    /// The prerequisite manifest has nothing for this system and architecture.
+    UnsupportedPlatform(String),
+    /// An entry of the prerequisite manifest cannot be used, such as a missing checksum.
+    InvalidManifest(String),
+    /// The media or an archive could not be extracted.
+    Extraction(String),
+    /// ffmpeg failed to convert the downloaded media.
+    Conversion(String),
+    Io(std::io::Error),
+    /// yt-dlp or ffmpeg is not installed in the libraries folder.
+    MissingPrerequisite(PathBuf),
+    /// A file written to the drive does not match the downloaded one, or cannot be decoded.
+    Verification(String),
+    /// The drive cannot hold the files, sizes are in bytes.
+    NotEnoughSpace { needed: u64, available: u64 },
+    /// A child process exited unsuccessfully, `code` is `None` when it was killed by a signal.
+    NonZeroExit { program: String, code: Option<i32> },
+    Cancelled,
+}
+
+impl Display for DownloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::Network(e) => write!(f, "Erreur réseau : {}", e),
+            DownloadError::ChecksumMismatch { url, expected, actual } => write!(
+                f,
+                "Fichier corrompu téléchargé depuis {} : SHA-256 {} au lieu de {}",
+                url, actual, expected
+            ),
+            DownloadError::UnsupportedPlatform(platform) => {
+                write!(f, "Aucun prérequis disponible pour {}", platform)
+            }
+            DownloadError::InvalidManifest(e) => write!(f, "Liste des prérequis invalide : {}", e),
+            DownloadError::Extraction(e) => write!(f, "Erreur d'extraction : {}", e),
+            DownloadError::Conversion(e) => write!(f, "Erreur de conversion : {}", e),
+            DownloadError::Io(e) if e.kind() == std::io::ErrorKind::StorageFull => {
+                write!(f, "La clé USB est pleine")
+            }
+            DownloadError::Io(e) => write!(f, "Erreur d'entrée/sortie : {}", e),
+            DownloadError::MissingPrerequisite(path) => {
+                write!(f, "Prérequis manquant : {}", path.display())
+            }
+            DownloadError::Verification(e) => write!(f, "Échec de la vérification : {}", e),
+            DownloadError::NotEnoughSpace { needed, available } => write!(
+                f,
+                "Espace insuffisant sur la clé USB : environ {} Mo nécessaires, {} Mo disponibles",
+                needed.div_ceil(MEGABYTE),
+                available / MEGABYTE
+            ),
+            DownloadError::NonZeroExit { program, code: Some(code) } => {
+                write!(f, "{} s'est terminé avec le code {}", program, code)
+            }
+            DownloadError::NonZeroExit { program, code: None } => {
+                write!(f, "{} a été interrompu", program)
+            }
+            DownloadError::Cancelled => write!(f, "Téléchargement annulé"),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DownloadError::Network(e) => Some(e),
+            DownloadError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(e: std::io::Error) -> Self {
+        DownloadError::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        DownloadError::Network(e)
+    }
+}
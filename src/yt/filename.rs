@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Longest file name accepted by FAT32, exFAT and most Linux filesystems, in bytes.
+pub const MAX_FILENAME_BYTES: usize = 255;
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "%(title)s";
+
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Turns a name rendered by yt-dlp into a file name that FAT32 and exFAT drives accept:
+/// forbidden and control characters are replaced or removed, reserved device names are
+/// escaped and the result, extension included, fits in [`MAX_FILENAME_BYTES`].
+pub fn sanitize(name: &str, extension: &str) -> String {
+    sanitize_with_suffix(name, &extension_suffix(extension))
+}
+
+/// Reserves a path in `directory` for a file named after the sanitized `name`, with the extension
+/// of `path`, by creating an empty file there. An existing file is never overwritten, a number is
+/// added to the name instead. Creating the file fails when the name is taken, even by a download
+/// finishing at the same time, so two callers never get the same path.
+pub fn reserve_path(path: &Path, directory: &Path, name: &str) -> std::io::Result<PathBuf> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut target = directory.join(sanitize(name, &extension));
+    let mut copy = 2;
+    loop {
+        match File::options().write(true).create_new(true).open(&target) {
+            Ok(_) => return Ok(target),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let suffix = format!(" ({}){}", copy, extension_suffix(&extension));
+                target = directory.join(sanitize_with_suffix(name, &suffix));
+                copy += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn extension_suffix(extension: &str) -> String {
+    if extension.is_empty() {
+        String::new()
+    } else {
+        format!(".{}", extension)
+    }
+}
+
+fn sanitize_with_suffix(name: &str, suffix: &str) -> String {
+    let mut sanitized = String::with_capacity(name.len());
+    for c in name.chars() {
+        let replacement = match c {
+            '"' => Some('\''),
+            ':' | '/' | '\\' | '|' => Some('-'),
+            '<' | '>' | '?' | '*' => None,
+            // Tabs and line breaks separate words, unlike the other control characters
+            c if c.is_whitespace() => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        };
+
+        match replacement {
+            // Collapse the whitespaces left by removed characters
+            Some(' ') if sanitized.is_empty() || sanitized.ends_with(' ') => {}
+            Some(c) => sanitized.push(c),
+            None => {}
+        }
+    }
+
+    let mut sanitized = trim(&sanitized).to_string();
+    if sanitized.is_empty() {
+        sanitized = String::from("Sans titre");
+    }
+    let device = sanitized.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(device)) {
+        sanitized.insert(device.len(), '_');
+    }
+
+    let sanitized = trim(truncate(&sanitized, MAX_FILENAME_BYTES.saturating_sub(suffix.len())));
+
+    format!("{}{}", sanitized, suffix)
+}
+
+/// Windows drops trailing dots and spaces, which would make two names collide.
+fn trim(name: &str) -> &str {
+    name.trim_start().trim_end_matches(['.', ' '])
+}
+
+fn truncate(name: &str, max_bytes: usize) -> &str {
+    if name.len() <= max_bytes {
+        return name;
+    }
+
+    let mut end = max_bytes;
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &name[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yt::test_dir;
+
+    #[test]
+    fn replaces_forbidden_characters() {
+        assert_eq!(sanitize("AC/DC: Back|In\\Black", "mp3"), "AC-DC- Back-In-Black.mp3");
+        assert_eq!(sanitize("Say \"Hello\" <now>?*", "mp3"), "Say 'Hello' now.mp3");
+        assert_eq!(sanitize("Tab\there\u{7}, new\nline", "opus"), "Tab here, new line.opus");
+        assert_eq!(sanitize("Two  * spaces", ""), "Two spaces");
+    }
+
+    #[test]
+    fn names_empty_titles() {
+        assert_eq!(sanitize("???", "mp3"), "Sans titre.mp3");
+        assert_eq!(sanitize(" . ", "mp3"), "Sans titre.mp3");
+    }
+
+    #[test]
+    fn drops_trailing_dots_and_spaces() {
+        assert_eq!(sanitize("  Titre... ", "mp3"), "Titre.mp3");
+        assert_eq!(sanitize("Titre. . .", ""), "Titre");
+    }
+
+    #[test]
+    fn escapes_reserved_device_names() {
+        assert_eq!(sanitize("con", "mp3"), "con_.mp3");
+        assert_eq!(sanitize("con.mp3", ""), "con_.mp3");
+        assert_eq!(sanitize("COM1 .live", "m4a"), "COM1_ .live.m4a");
+        assert_eq!(sanitize("Lpt9", "mp3"), "Lpt9_.mp3");
+        assert_eq!(sanitize("Console", "mp3"), "Console.mp3");
+        assert_eq!(sanitize("COM10", "mp3"), "COM10.mp3");
+    }
+
+    #[test]
+    fn truncates_long_names_on_a_char_boundary() {
+        let sanitized = sanitize(&"é".repeat(200), "mp3");
+        assert_eq!(sanitized, format!("{}.mp3", "é".repeat(125)));
+        assert!(sanitized.len() <= MAX_FILENAME_BYTES);
+
+        let sanitized = sanitize(&"😀".repeat(100), "flac");
+        assert_eq!(sanitized, format!("{}.flac", "😀".repeat(62)));
+
+        // The cut must not leave a trailing space
+        let sanitized = sanitize(&format!("{} end", "a".repeat(250)), "mp3");
+        assert_eq!(sanitized, format!("{}.mp3", "a".repeat(250)));
+    }
+
+    #[test]
+    fn reserves_a_numbered_name_when_taken() {
+        let directory = test_dir("filename-reserve");
+        let path = Path::new("work/abc.mp3");
+        std::fs::write(directory.join("Titre.mp3"), "existing").unwrap();
+
+        assert_eq!(reserve_path(path, &directory, "Titre").unwrap(), directory.join("Titre (2).mp3"));
+        assert_eq!(reserve_path(path, &directory, "Titre").unwrap(), directory.join("Titre (3).mp3"));
+        assert_eq!(std::fs::read_to_string(directory.join("Titre.mp3")).unwrap(), "existing");
+
+        let long_name = "a".repeat(300);
+        reserve_path(path, &directory, &long_name).unwrap();
+        let reserved = reserve_path(path, &directory, &long_name).unwrap();
+        let file_name = reserved.file_name().unwrap().to_string_lossy().into_owned();
+        assert_eq!(file_name, format!("{} (2).mp3", "a".repeat(MAX_FILENAME_BYTES - 8)));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::yt::{AudioFormat, AudioQuality, CancelHandle, DownloadError, ffmpeg_error_line, new_command};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Loudness most streaming services play music at, in LUFS.
+pub const DEFAULT_TARGET_LUFS: f64 = -14.0;
+pub const MIN_TARGET_LUFS: f64 = -30.0;
+pub const MAX_TARGET_LUFS: f64 = -5.0;
+/// Highest true peak, in dBTP, leaving room for the lossy encoders.
+const TRUE_PEAK: f64 = -1.5;
+const LOUDNESS_RANGE: f64 = 11.0;
+/// loudnorm works at 192 kHz, the output is brought back to the input rate or to this one.
+const DEFAULT_SAMPLE_RATE: u32 = 48000;
+const NORMALIZED_SUFFIX: &str = ".normalized";
+
+/// Two pass EBU R128 normalisation of the audio files with ffmpeg's `loudnorm` filter:
+/// the first pass measures the file, the second one applies a linear gain computed from it.
+#[derive(Debug, Clone)]
+pub struct Normalization {
+    pub ffmpeg_path: PathBuf,
+    pub target_lufs: f64,
+    pub audio_format: AudioFormat,
+    pub audio_quality: AudioQuality,
+}
+
+/// Loudness printed by the measuring pass, ffmpeg writes the numbers as strings.
+#[derive(Debug, Deserialize)]
+struct Measure {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+impl Normalization {
+    /// Normalises the file at `path` in place, it is only replaced once the new one is complete.
+    pub fn apply(&self, path: &Path, cancel_handle: &CancelHandle) -> Result<(), DownloadError> {
+        let (measure, sample_rate) = self.measure(path, cancel_handle)?;
+
+        let mut normalized = path.as_os_str().to_owned();
+        normalized.push(NORMALIZED_SUFFIX);
+        if let Some(extension) = path.extension() {
+            normalized.push(".");
+            normalized.push(extension);
+        }
+        let normalized = PathBuf::from(normalized);
+
+        let filter = format!(
+            "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            self.filter(),
+            measure.input_i,
+            measure.input_tp,
+            measure.input_lra,
+            measure.input_thresh,
+            measure.target_offset,
+        );
+        let output = cancel_handle.output(
+            new_command(&self.ffmpeg_path)
+                .args(["-hide_banner", "-nostdin", "-nostats", "-y", "-i"])
+                .arg(path)
+                // The cover art and the tags are copied as they are
+                .args(["-map", "0:a", "-map", "0:v?", "-c:v", "copy", "-map_metadata", "0"])
+                .args(["-af", filter.as_str(), "-ar", sample_rate.to_string().as_str()])
+                .args(["-c:a", self.audio_format.ffmpeg_codec()])
+                .args(self.audio_quality.ffmpeg_args(self.audio_format))
+                .arg(&normalized),
+        )?;
+
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&normalized);
+            return Err(ffmpeg_error(&output.stderr));
+        }
+        std::fs::rename(&normalized, path)?;
+
+        Ok(())
+    }
+
+    fn filter(&self) -> String {
+        format!(
+            "loudnorm=I={}:TP={}:LRA={}",
+            self.target_lufs.clamp(MIN_TARGET_LUFS, MAX_TARGET_LUFS),
+            TRUE_PEAK,
+            LOUDNESS_RANGE
+        )
+    }
+
+    /// Runs the measuring pass, also returning the sample rate of the file.
+    fn measure(&self, path: &Path, cancel_handle: &CancelHandle) -> Result<(Measure, u32), DownloadError> {
+        let output = cancel_handle.output(
+            new_command(&self.ffmpeg_path)
+                .args(["-hide_banner", "-nostdin", "-nostats", "-i"])
+                .arg(path)
+                .args(["-map", "0:a", "-af"])
+                .arg(format!("{}:print_format=json", self.filter()))
+                .args(["-f", "null", "-"]),
+        )?;
+        if !output.status.success() {
+            return Err(ffmpeg_error(&output.stderr));
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // The measure is the last JSON object printed
+        let json = stderr
+            .rfind('{')
+            .map(|start| &stderr[start..])
+            .and_then(|json| Some(&json[..=json.rfind('}')?]))
+            .ok_or_else(|| DownloadError::Conversion(String::from("mesure du volume introuvable")))?;
+        let measure = serde_json::from_str(json).map_err(|e| DownloadError::Conversion(e.to_string()))?;
+
+        Ok((measure, sample_rate(&stderr).unwrap_or(DEFAULT_SAMPLE_RATE)))
+    }
+}
+
+/// Reads the rate of the first audio stream, such as `44100` in
+/// `Stream #0:0: Audio: mp3, 44100 Hz, stereo, fltp, 128 kb/s`.
+fn sample_rate(stderr: &str) -> Option<u32> {
+    stderr
+        .lines()
+        .find_map(|line| line.split_once("Audio:"))?
+        .1
+        .split(',')
+        .find_map(|field| field.trim().strip_suffix(" Hz")?.parse().ok())
+}
+
+fn ffmpeg_error(stderr: &[u8]) -> DownloadError {
+    DownloadError::Conversion(format!("normalisation du volume impossible : {}", ffmpeg_error_line(stderr)))
+}
@@ -0,0 +1,86 @@
+use crate::yt::{DownloadError, YoutubeDownloader, new_command, process, url};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Information about a video, as printed by `yt-dlp --dump-json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VideoInfo {
+    pub id: String,
+    pub title: String,
+    channel: Option<String>,
+    uploader: Option<String>,
+    duration: Option<f64>,
+    filesize: Option<f64>,
+    filesize_approx: Option<f64>,
+}
+
+/// Results of a `ytsearchN:` query, as listed by `yt-dlp --flat-playlist --dump-single-json`.
+#[derive(Debug, Clone, Deserialize)]
+struct SearchResults {
+    #[serde(default)]
+    entries: Vec<VideoInfo>,
+}
+
+impl VideoInfo {
+    pub fn channel(&self) -> Option<&str> {
+        self.channel.as_deref().or(self.uploader.as_deref())
+    }
+
+    /// `None` for live streams.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+            .filter(|duration| duration.is_finite() && *duration >= 0.0)
+            .map(Duration::from_secs_f64)
+    }
+
+    /// Size of the audio stream yt-dlp would download, estimated from its bitrate
+    /// when YouTube does not give it.
+    pub fn estimated_size(&self) -> Option<u64> {
+        self.filesize.or(self.filesize_approx).map(|size| size as u64)
+    }
+
+    pub fn url(&self) -> String {
+        url::watch_url(&self.id)
+    }
+
+    /// Medium size thumbnail, always available as a JPEG image.
+    pub fn thumbnail_url(&self) -> String {
+        format!("https://i.ytimg.com/vi/{}/mqdefault.jpg", self.id)
+    }
+}
+
+impl YoutubeDownloader {
+    /// Reads the information of a video without downloading it.
+    pub fn fetch_video_info(&self, url: &str) -> Result<VideoInfo, DownloadError> {
+        self.require_prerequisites()?;
+
+        let output = new_command(&self.yt_dlp_path)
+            .args([url, "--dump-json", "--skip-download", "--no-playlist", "-f", "bestaudio/best"])
+            .output()?;
+        let json = process::output_to_string(output)?;
+
+        serde_json::from_str(&json).map_err(|e| DownloadError::Extraction(e.to_string()))
+    }
+
+    /// Searches YouTube for `query`, returning at most `count` videos.
+    /// The results are not resolved one by one, so their size is unknown.
+    pub fn search(&self, query: &str, count: usize) -> Result<Vec<VideoInfo>, DownloadError> {
+        self.require_prerequisites()?;
+
+        let output = new_command(&self.yt_dlp_path)
+            .arg(format!("ytsearch{}:{}", count, query))
+            .args(["--flat-playlist", "--dump-single-json"])
+            .output()?;
+        let json = process::output_to_string(output)?;
+        let results: SearchResults =
+            serde_json::from_str(&json).map_err(|e| DownloadError::Extraction(e.to_string()))?;
+
+        Ok(results.entries)
+    }
+}
+
+pub async fn fetch_thumbnail(url: &str) -> Result<Vec<u8>, DownloadError> {
+    let thumbnail = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+
+    Ok(thumbnail.to_vec())
+}
@@ -0,0 +1,157 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AudioFormat {
+    #[default]
+    Mp3,
+    M4a,
+    Opus,
+    Flac,
+    Ogg,
+    Wav,
+}
+
+/// Constant bitrate in kbit/s, or variable bitrate level from 0 (best) to 9 (smallest).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioQuality {
+    Cbr(u32),
+    Vbr(u8),
+}
+
+impl AudioFormat {
+    pub const ALL: [AudioFormat; 6] = [
+        AudioFormat::Mp3,
+        AudioFormat::M4a,
+        AudioFormat::Opus,
+        AudioFormat::Flac,
+        AudioFormat::Ogg,
+        AudioFormat::Wav,
+    ];
+
+    /// Value of yt-dlp's `--audio-format` argument.
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::M4a => "m4a",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Ogg => "vorbis",
+            AudioFormat::Wav => "wav",
+        }
+    }
+
+    /// Extension of the files yt-dlp writes in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Ogg => "ogg",
+            _ => self.as_arg(),
+        }
+    }
+
+    /// Lossless formats ignore the requested [`AudioQuality`].
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, AudioFormat::Flac | AudioFormat::Wav)
+    }
+
+    /// ffmpeg encoder yt-dlp uses for this format.
+    pub fn ffmpeg_codec(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "libmp3lame",
+            AudioFormat::M4a => "aac",
+            AudioFormat::Opus => "libopus",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Ogg => "libvorbis",
+            AudioFormat::Wav => "pcm_s16le",
+        }
+    }
+
+    /// Approximate bitrate of the files written in this format, in kbit/s, used to estimate
+    /// their size before downloading them.
+    pub fn estimated_bitrate(&self, quality: AudioQuality) -> u32 {
+        // Average bitrates of LAME's variable bitrate levels, the other encoders are close to them
+        const VBR_BITRATES: [u32; 10] = [245, 225, 190, 175, 165, 130, 115, 100, 85, 65];
+
+        match (self, quality) {
+            // 48 kHz stereo, the sample rate of YouTube's audio
+            (AudioFormat::Wav, _) => 1536,
+            (AudioFormat::Flac, _) => 1000,
+            (_, AudioQuality::Cbr(bitrate)) => bitrate,
+            (_, AudioQuality::Vbr(level)) => VBR_BITRATES[(level as usize).min(VBR_BITRATES.len() - 1)],
+        }
+    }
+
+    /// yt-dlp cannot embed a thumbnail in WAV files.
+    pub fn supports_cover_art(&self) -> bool {
+        *self != AudioFormat::Wav
+    }
+}
+
+impl Display for AudioFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension().to_uppercase())
+    }
+}
+
+impl AudioQuality {
+    pub const ALL: [AudioQuality; 13] = [
+        AudioQuality::Cbr(128),
+        AudioQuality::Cbr(192),
+        AudioQuality::Cbr(320),
+        AudioQuality::Vbr(0),
+        AudioQuality::Vbr(1),
+        AudioQuality::Vbr(2),
+        AudioQuality::Vbr(3),
+        AudioQuality::Vbr(4),
+        AudioQuality::Vbr(5),
+        AudioQuality::Vbr(6),
+        AudioQuality::Vbr(7),
+        AudioQuality::Vbr(8),
+        AudioQuality::Vbr(9),
+    ];
+
+    /// Value of yt-dlp's `--audio-quality` argument.
+    pub fn as_arg(&self) -> String {
+        match self {
+            AudioQuality::Cbr(bitrate) => format!("{}K", bitrate),
+            AudioQuality::Vbr(level) => level.to_string(),
+        }
+    }
+
+    /// ffmpeg arguments giving this quality to `format`, the same as yt-dlp's: a variable
+    /// bitrate level is scaled to the range of each encoder, those without one use their default.
+    pub fn ffmpeg_args(&self, format: AudioFormat) -> Vec<String> {
+        if format.is_lossless() {
+            return Vec::new();
+        }
+
+        match (self, format) {
+            (AudioQuality::Cbr(bitrate), _) => vec![String::from("-b:a"), format!("{}k", bitrate)],
+            (AudioQuality::Vbr(level), AudioFormat::Mp3) => vec![String::from("-q:a"), level.to_string()],
+            (AudioQuality::Vbr(level), AudioFormat::Ogg) => {
+                vec![String::from("-q:a"), (10 - *level as i32).to_string()]
+            }
+            (AudioQuality::Vbr(level), AudioFormat::M4a) => {
+                vec![String::from("-q:a"), format!("{}", 4.0 - 3.9 * *level as f64 / 10.0)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// yt-dlp's own default quality.
+impl Default for AudioQuality {
+    fn default() -> Self {
+        AudioQuality::Vbr(5)
+    }
+}
+
+impl Display for AudioQuality {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioQuality::Cbr(bitrate) => write!(f, "CBR {} kb/s", bitrate),
+            AudioQuality::Vbr(0) => write!(f, "VBR V0 (meilleure qualité)"),
+            AudioQuality::Vbr(9) => write!(f, "VBR V9 (plus petite taille)"),
+            AudioQuality::Vbr(level) => write!(f, "VBR V{}", level),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::yt::progress::DownloadProgress;
+use crate::yt::{
+    CancelHandle, DownloadArchive, DownloadError, DownloadedFile, YoutubeDownloader, new_command,
+    process, url,
+};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// Playlist as listed by `yt-dlp --flat-playlist --dump-single-json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Playlist {
+    #[serde(default)]
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaylistEntry {
+    pub id: String,
+    pub title: Option<String>,
+    /// In seconds, unknown for some videos such as live streams.
+    pub duration: Option<f64>,
+}
+
+#[derive(Debug)]
+pub enum PlaylistEvent {
+    TrackStarted {
+        index: usize,
+        count: usize,
+        title: String,
+    },
+    TrackProgress(DownloadProgress),
+}
+
+/// Outcome of a playlist download, the tracks that failed are listed with their cause.
+/// `skipped` counts the tracks found in the download archive of the drive, and `cancelled`
+/// is set when the user stopped the download before the last track.
+#[derive(Debug, Default)]
+pub struct PlaylistReport {
+    pub downloaded: Vec<DownloadedFile>,
+    pub skipped: usize,
+    pub failed: Vec<(PlaylistEntry, DownloadError)>,
+    pub cancelled: bool,
+}
+
+impl PlaylistEntry {
+    pub fn url(&self) -> String {
+        url::watch_url(&self.id)
+    }
+
+    pub fn display_title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.id)
+    }
+}
+
+impl YoutubeDownloader {
+    /// Lists the videos of a playlist without downloading them.
+    pub fn fetch_playlist(&self, url: &str) -> Result<Playlist, DownloadError> {
+        self.require_prerequisites()?;
+
+        let output = new_command(&self.yt_dlp_path)
+            .args([url, "--flat-playlist", "--dump-single-json"])
+            .output()?;
+        let json = process::output_to_string(output)?;
+
+        serde_json::from_str(&json).map_err(|e| DownloadError::Extraction(e.to_string()))
+    }
+
+    /// Approximate size of the tracks of `playlist` that would be written to `out_path`,
+    /// those found in the download archive of the drive excluded.
+    pub fn estimated_playlist_size(&self, playlist: &Playlist, out_path: &Path) -> u64 {
+        let archive = self
+            .use_download_archive
+            .then(|| DownloadArchive::open(out_path));
+
+        playlist
+            .entries
+            .iter()
+            .filter(|entry| !archive.as_ref().is_some_and(|archive| archive.contains(&entry.id)))
+            .filter_map(|entry| entry.duration)
+            .filter(|duration| duration.is_finite() && *duration >= 0.0)
+            .map(|duration| self.estimated_size(Duration::from_secs_f64(duration)))
+            .sum()
+    }
+
+    /// Downloads the tracks of `playlist` one after the other. A track failing to download
+    /// does not stop the others, only a cancellation does. The tracks downloaded before the
+    /// cancellation are still reported.
+    pub fn download_playlist(
+        &mut self,
+        playlist: &Playlist,
+        out_path: &Path,
+        cancel_handle: &CancelHandle,
+        mut on_event: impl FnMut(PlaylistEvent),
+    ) -> PlaylistReport {
+        let mut report = PlaylistReport::default();
+        // Checking the archive first spares starting yt-dlp for every track already on the drive
+        let archive = self
+            .use_download_archive
+            .then(|| DownloadArchive::open(out_path));
+
+        for (index, entry) in playlist.entries.iter().enumerate() {
+            if cancel_handle.is_cancelled() {
+                report.cancelled = true;
+                break;
+            }
+            if archive.as_ref().is_some_and(|archive| archive.contains(&entry.id)) {
+                report.skipped += 1;
+                continue;
+            }
+
+            on_event(PlaylistEvent::TrackStarted {
+                index,
+                count: playlist.entries.len(),
+                title: entry.display_title().to_string(),
+            });
+            let result = self.download(entry.url(), out_path, None).and_then(|process| {
+                let mut process = process.with_cancel_handle(cancel_handle.clone());
+                if let Some(progress) = process.progress() {
+                    for progress in progress {
+                        on_event(PlaylistEvent::TrackProgress(progress));
+                    }
+                }
+                process.wait_with_progress(|progress| on_event(PlaylistEvent::TrackProgress(progress)))
+            });
+
+            match result {
+                Ok(files) if files.is_empty() => report.skipped += 1,
+                Ok(files) => report.downloaded.extend(files),
+                Err(DownloadError::Cancelled) => {
+                    report.cancelled = true;
+                    break;
+                }
+                Err(e) => report.failed.push((entry.clone(), e)),
+            }
+        }
+
+        report
+    }
+}
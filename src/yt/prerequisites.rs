@@ -0,0 +1,662 @@
+use crate::yt::DownloadError;
+use crate::yt::verify::{self, Checksum};
+use reqwest::header::{ETAG, HeaderName, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[cfg(target_os = "windows")]
+use sevenz_rust::{Password, SevenZReader};
+
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(target_os = "linux")]
+use tar::Archive;
+#[cfg(target_os = "linux")]
+use xz2::read::XzDecoder;
+
+/// Versions of yt-dlp and ffmpeg the application installs, with the SHA-256 published for each
+/// file. Moving to another version only means editing this file.
+const BUNDLED_MANIFEST: &str = include_str!("prerequisites.json");
+/// Record of the prerequisites installed in the libraries folder, a copy of their manifest entry.
+const INSTALLED_FILE_NAME: &str = "installed.json";
+/// Extension of a file being downloaded, it is only renamed once its checksum is verified.
+const DOWNLOAD_EXTENSION: &str = ".download";
+/// Extension of the [`PartialDownload`] kept next to an interrupted download.
+const RESUME_EXTENSION: &str = ".resume";
+/// A connection silent for this long is dropped, the download is resumed on the next attempt.
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Bytes received between two progress reports, a chunk is only a few kilobytes.
+const PROGRESS_STEP: u64 = 256 * 1024;
+
+/// A file pinned to a version, installed only when its SHA-256 is the expected one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub version: String,
+    pub url: String,
+    /// Digest in hexadecimal, as published next to the releases.
+    pub sha256: String,
+}
+
+/// The prerequisites of a platform, ffmpeg being an archive its executable is extracted from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Prerequisites {
+    #[serde(rename = "yt-dlp")]
+    pub yt_dlp: Artifact,
+    pub ffmpeg: Artifact,
+}
+
+/// Progress of the prerequisite being downloaded, sizes are in bytes.
+#[derive(Debug, Clone, Default)]
+pub struct InstallProgress {
+    /// Name of the prerequisite, such as `ffmpeg`.
+    pub name: &'static str,
+    pub downloaded_bytes: u64,
+    /// `None` when the server does not give the size of the file.
+    pub total_bytes: Option<u64>,
+}
+
+/// What identifies the file on the server when its download was started, an interrupted download
+/// is only resumed while the server still has the same file.
+#[derive(Debug, Serialize, Deserialize)]
+struct PartialDownload {
+    url: String,
+    /// Strong ETag, or else Last-Modified date, sent back in `If-Range`.
+    validator: String,
+}
+
+/// The prerequisites of each platform, keyed by system and architecture such as `linux-x86_64`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest(HashMap<String, Prerequisites>);
+
+impl Manifest {
+    /// The manifest built into the application.
+    pub fn bundled() -> Self {
+        Self::from_json(BUNDLED_MANIFEST).expect("the bundled prerequisite manifest is valid")
+    }
+
+    /// Reads a manifest in the format of the bundled one, such as one listing a local server.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// The prerequisites of the platform the application runs on.
+    pub fn current_platform(&self) -> Result<&Prerequisites, DownloadError> {
+        let platform = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+        self.0.get(&platform).ok_or(DownloadError::UnsupportedPlatform(platform))
+    }
+}
+
+impl Prerequisites {
+    /// Whether `libs_folder` holds these versions, as recorded by the last install. Files installed
+    /// by an older version of the application, or replaced since, are installed again.
+    pub fn is_installed(&self, libs_folder: &Path) -> bool {
+        std::fs::read_to_string(libs_folder.join(INSTALLED_FILE_NAME))
+            .ok()
+            .and_then(|json| serde_json::from_str::<Prerequisites>(&json).ok())
+            .is_some_and(|installed| installed == *self)
+    }
+
+    fn record_installed(&self, libs_folder: &Path) -> Result<(), DownloadError> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::from)?;
+        Ok(std::fs::write(libs_folder.join(INSTALLED_FILE_NAME), json)?)
+    }
+}
+
+impl Artifact {
+    /// The published digest, a manifest entry without one cannot be installed.
+    fn expected_checksum(&self) -> Result<&str, DownloadError> {
+        let sha256 = self.sha256.trim();
+        if sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(sha256)
+        } else {
+            Err(DownloadError::InvalidManifest(format!(
+                "SHA-256 absent ou mal formé pour {} {}",
+                self.url, self.version
+            )))
+        }
+    }
+}
+
+impl InstallProgress {
+    pub fn fraction(&self) -> Option<f64> {
+        self.total_bytes
+            .filter(|total| *total > 0)
+            .map(|total| (self.downloaded_bytes as f64 / total as f64).min(1.0))
+    }
+}
+
+/// Downloads the prerequisites into `libs_folder`, no file is installed before its checksum is verified.
+/// `on_progress` is given the progress of each download.
+#[cfg(target_os = "linux")]
+pub async fn install(
+    libs_folder: &Path,
+    prerequisites: &Prerequisites,
+    mut on_progress: impl FnMut(InstallProgress),
+) -> Result<(), DownloadError> {
+    std::fs::create_dir_all(libs_folder)?;
+    let client = new_client()?;
+    let yt_dlp_path = libs_folder.join("yt-dlp");
+    download(&client, "yt-dlp", &prerequisites.yt_dlp, &yt_dlp_path, &mut on_progress).await?;
+    let archive_path = libs_folder.join("ffmpeg.tar.xz");
+    download(&client, "ffmpeg", &prerequisites.ffmpeg, &archive_path, &mut on_progress).await?;
+
+    let ffmpeg_path = libs_folder.join("ffmpeg");
+    let result = extract_ffmpeg(&archive_path, &ffmpeg_path);
+    let _ = std::fs::remove_file(&archive_path);
+    result?;
+
+    std::fs::set_permissions(&ffmpeg_path, std::fs::Permissions::from_mode(0o744))?;
+    std::fs::set_permissions(&yt_dlp_path, std::fs::Permissions::from_mode(0o744))?;
+
+    prerequisites.record_installed(libs_folder)
+}
+
+/// Downloads the prerequisites into `libs_folder`, no file is installed before its checksum is verified.
+/// `on_progress` is given the progress of each download.
+#[cfg(target_os = "windows")]
+pub async fn install(
+    libs_folder: &Path,
+    prerequisites: &Prerequisites,
+    mut on_progress: impl FnMut(InstallProgress),
+) -> Result<(), DownloadError> {
+    std::fs::create_dir_all(libs_folder)?;
+    let client = new_client()?;
+    let yt_dlp_path = libs_folder.join("yt-dlp.exe");
+    download(&client, "yt-dlp", &prerequisites.yt_dlp, &yt_dlp_path, &mut on_progress).await?;
+    let archive_path = libs_folder.join("ffmpeg.7z");
+    download(&client, "ffmpeg", &prerequisites.ffmpeg, &archive_path, &mut on_progress).await?;
+
+    let result = extract_ffmpeg(&archive_path, &libs_folder.join("ffmpeg.exe"));
+    let _ = std::fs::remove_file(&archive_path);
+    result?;
+
+    prerequisites.record_installed(libs_folder)
+}
+
+fn new_client() -> Result<Client, DownloadError> {
+    Ok(Client::builder().connect_timeout(TIMEOUT).read_timeout(TIMEOUT).build()?)
+}
+
+/// Downloads `artifact` to `path`, a file with another checksum is deleted instead. An interrupted
+/// download stays in the libraries folder and is resumed by the next call.
+async fn download(
+    client: &Client,
+    name: &'static str,
+    artifact: &Artifact,
+    path: &Path,
+    on_progress: &mut impl FnMut(InstallProgress),
+) -> Result<(), DownloadError> {
+    // Nothing could be verified, better not download it at all
+    artifact.expected_checksum()?;
+    let partial = with_suffix(path, DOWNLOAD_EXTENSION);
+    let resume_path = with_suffix(path, RESUME_EXTENSION);
+    fetch(client, name, artifact, &partial, &resume_path, on_progress).await?;
+    let _ = std::fs::remove_file(&resume_path);
+
+    // A corrupted file is not resumed, the next attempt starts over
+    let result = verify::checksum(&partial)
+        .map_err(DownloadError::from)
+        .and_then(|checksum| check(artifact, &checksum))
+        .and_then(|()| Ok(std::fs::rename(&partial, path)?));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Writes `artifact` to `partial` as it arrives, the archives are too large to be kept in memory.
+/// What `partial` already holds is kept when the server can send the rest of the same file.
+async fn fetch(
+    client: &Client,
+    name: &'static str,
+    artifact: &Artifact,
+    partial: &Path,
+    resume_path: &Path,
+    on_progress: &mut impl FnMut(InstallProgress),
+) -> Result<(), DownloadError> {
+    let resumed = std::fs::read_to_string(resume_path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<PartialDownload>(&json).ok())
+        .filter(|resumed| resumed.url == artifact.url);
+    let offset = match &resumed {
+        Some(_) => std::fs::metadata(partial).map(|metadata| metadata.len()).unwrap_or_default(),
+        None => 0,
+    };
+
+    let mut request = client.get(&artifact.url);
+    if let Some(resumed) = resumed.as_ref().filter(|_| offset > 0) {
+        // The server sends the whole file instead when it changed since
+        request = request
+            .header(RANGE, format!("bytes={}-", offset))
+            .header(IF_RANGE, &resumed.validator);
+    }
+    let response = request.send().await?;
+
+    let (mut response, mut file, mut progress) = match response.status() {
+        StatusCode::PARTIAL_CONTENT if offset > 0 => {
+            let progress = InstallProgress {
+                name,
+                downloaded_bytes: offset,
+                total_bytes: response.content_length().map(|length| offset + length),
+            };
+            (response, OpenOptions::new().append(true).open(partial)?, progress)
+        }
+        // The previous attempt was interrupted once the whole file was received
+        StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => return Ok(()),
+        _ => {
+            let response = response.error_for_status()?;
+            match validator(&response) {
+                Some(validator) => {
+                    let resumed = PartialDownload {
+                        url: artifact.url.clone(),
+                        validator,
+                    };
+                    let json = serde_json::to_string(&resumed).map_err(std::io::Error::from)?;
+                    std::fs::write(resume_path, json)?;
+                }
+                // Without a validator, a partial file could be completed with another version
+                None => {
+                    let _ = std::fs::remove_file(resume_path);
+                }
+            }
+            let progress = InstallProgress {
+                name,
+                downloaded_bytes: 0,
+                total_bytes: response.content_length(),
+            };
+            (response, File::create(partial)?, progress)
+        }
+    };
+    on_progress(progress.clone());
+
+    let mut reported_bytes = progress.downloaded_bytes;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk)?;
+        progress.downloaded_bytes += chunk.len() as u64;
+        if progress.downloaded_bytes - reported_bytes >= PROGRESS_STEP {
+            reported_bytes = progress.downloaded_bytes;
+            on_progress(progress.clone());
+        }
+    }
+    on_progress(progress);
+
+    Ok(())
+}
+
+/// The strong ETag of the response, or else its Last-Modified date. Weak ETags cannot be used in `If-Range`.
+fn validator(response: &reqwest::Response) -> Option<String> {
+    let header = |name: HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+
+    header(ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(LAST_MODIFIED))
+}
+
+fn check(artifact: &Artifact, checksum: &Checksum) -> Result<(), DownloadError> {
+    let expected = artifact.expected_checksum()?;
+    let actual: String = checksum.iter().map(|byte| format!("{:02x}", byte)).collect();
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(())
+    } else {
+        Err(DownloadError::ChecksumMismatch {
+            url: artifact.url.clone(),
+            expected: expected.to_string(),
+            actual,
+        })
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path.as_os_str());
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+#[cfg(target_os = "linux")]
+fn extract_ffmpeg(archive_path: &Path, ffmpeg_path: &Path) -> Result<(), DownloadError> {
+    let extraction_error = |e: std::io::Error| DownloadError::Extraction(e.to_string());
+    let mut archive = Archive::new(XzDecoder::new(File::open(archive_path)?));
+    for entry in archive.entries().map_err(extraction_error)? {
+        let mut entry = entry.map_err(extraction_error)?;
+        // The archive folder is named after ffmpeg too, only its executable is wanted
+        if entry.path().map_err(extraction_error)?.file_name().is_some_and(|name| name == "ffmpeg") {
+            let mut output_file = File::create(ffmpeg_path)?;
+            std::io::copy(&mut entry, &mut output_file).map_err(extraction_error)?;
+            return Ok(());
+        }
+    }
+
+    Err(DownloadError::Extraction(String::from("ffmpeg est absent de l'archive")))
+}
+
+#[cfg(target_os = "windows")]
+fn extract_ffmpeg(archive_path: &Path, ffmpeg_path: &Path) -> Result<(), DownloadError> {
+    let extraction_error = |e: sevenz_rust::Error| DownloadError::Extraction(e.to_string());
+    let mut archive = SevenZReader::open(archive_path, Password::empty()).map_err(extraction_error)?;
+    archive
+        .for_each_entries(|entry, r| {
+            if entry.name().ends_with("ffmpeg.exe") {
+                let mut output_file = File::create(ffmpeg_path)?;
+                std::io::copy(r, &mut output_file)?;
+            }
+            Ok(true)
+        })
+        .map_err(extraction_error)?;
+
+    if ffmpeg_path.exists() {
+        Ok(())
+    } else {
+        Err(DownloadError::Extraction(String::from("ffmpeg est absent de l'archive")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yt::test_dir;
+    use sha2::{Digest, Sha256};
+    use std::io::{BufRead, BufReader};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+
+    /// Content and ETag of the files served, by path.
+    type ServedFiles = Mutex<HashMap<String, (Vec<u8>, String)>>;
+
+    /// A local HTTP server answering range requests as the release servers do, one request
+    /// per connection. The `Range` header of each request is recorded.
+    struct TestServer {
+        address: SocketAddr,
+        files: Arc<ServedFiles>,
+        ranges: Arc<Mutex<Vec<Option<String>>>>,
+    }
+
+    impl TestServer {
+        fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let server = Self {
+                address: listener.local_addr().unwrap(),
+                files: Arc::default(),
+                ranges: Arc::default(),
+            };
+
+            let (files, ranges) = (server.files.clone(), server.ranges.clone());
+            std::thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let _ = respond(stream, &files, &ranges);
+                }
+            });
+            server
+        }
+
+        /// Serves `content` at `path`, under a strong `etag`.
+        fn serve(&self, path: &str, content: &[u8], etag: &str) {
+            self.files
+                .lock()
+                .unwrap()
+                .insert(path.to_string(), (content.to_vec(), etag.to_string()));
+        }
+
+        fn url(&self, path: &str) -> String {
+            format!("http://{}/{}", self.address, path)
+        }
+
+        fn ranges(&self) -> Vec<Option<String>> {
+            self.ranges.lock().unwrap().clone()
+        }
+    }
+
+    fn respond(
+        mut stream: TcpStream,
+        files: &ServedFiles,
+        ranges: &Mutex<Vec<Option<String>>>,
+    ) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let path = request_line.split(' ').nth(1).unwrap_or_default().trim_start_matches('/');
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            match line.trim_end().split_once(':') {
+                Some((name, value)) => headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string()),
+                None => break,
+            };
+        }
+        ranges.lock().unwrap().push(headers.get("range").cloned());
+
+        let files = files.lock().unwrap();
+        let Some((content, etag)) = files.get(path) else {
+            return stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        };
+        // The whole file is sent again when it changed since the partial download started
+        let start = headers
+            .get("range")
+            .filter(|_| headers.get("if-range") == Some(etag))
+            .and_then(|range| range.strip_prefix("bytes=")?.strip_suffix('-')?.parse::<usize>().ok());
+        let (status, body, content_range) = match start {
+            Some(start) if start >= content.len() => {
+                ("416 Range Not Satisfiable", &[][..], format!("Content-Range: bytes */{}\r\n", content.len()))
+            }
+            Some(start) => (
+                "206 Partial Content",
+                &content[start..],
+                format!("Content-Range: bytes {}-{}/{}\r\n", start, content.len() - 1, content.len()),
+            ),
+            None => ("200 OK", &content[..], String::new()),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nETag: {}\r\n{}Connection: close\r\n\r\n",
+            status,
+            body.len(),
+            etag,
+            content_range
+        )?;
+        stream.write_all(body)
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn sha256(content: &[u8]) -> String {
+        Sha256::digest(content).iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn artifact(sha256: &str) -> Artifact {
+        Artifact {
+            version: String::from("1.0"),
+            url: String::from("http://127.0.0.1/yt-dlp"),
+            sha256: sha256.to_string(),
+        }
+    }
+
+    fn served_artifact(server: &TestServer, path: &str, content: &[u8]) -> Artifact {
+        Artifact {
+            url: server.url(path),
+            ..artifact(&sha256(content))
+        }
+    }
+
+    fn download_to(path: &Path, artifact: &Artifact) -> Result<(), DownloadError> {
+        block_on(async { download(&new_client()?, "yt-dlp", artifact, path, &mut |_| {}).await })
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn installs_verified_prerequisites() {
+        let libs_folder = test_dir("prerequisites-install");
+        let yt_dlp = b"#!/bin/sh\necho yt-dlp\n".to_vec();
+        let ffmpeg = b"#!/bin/sh\necho ffmpeg\n".to_vec();
+        let mut archive = tar::Builder::new(xz2::write::XzEncoder::new(Vec::new(), 6));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(ffmpeg.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        archive.append_data(&mut header, "ffmpeg-7.0.2-amd64-static/ffmpeg", &ffmpeg[..]).unwrap();
+        let archive = archive.into_inner().unwrap().finish().unwrap();
+
+        let server = TestServer::start();
+        server.serve("yt-dlp", &yt_dlp, "\"1\"");
+        server.serve("ffmpeg.tar.xz", &archive, "\"2\"");
+        let prerequisites = Prerequisites {
+            yt_dlp: served_artifact(&server, "yt-dlp", &yt_dlp),
+            ffmpeg: served_artifact(&server, "ffmpeg.tar.xz", &archive),
+        };
+        block_on(install(&libs_folder, &prerequisites, |_| {})).unwrap();
+
+        assert_eq!(std::fs::read(libs_folder.join("yt-dlp")).unwrap(), yt_dlp);
+        assert_eq!(std::fs::read(libs_folder.join("ffmpeg")).unwrap(), ffmpeg);
+        assert!(!libs_folder.join("ffmpeg.tar.xz").exists());
+        assert!(prerequisites.is_installed(&libs_folder));
+    }
+
+    #[test]
+    fn deletes_downloads_with_another_checksum() {
+        let path = test_dir("prerequisites-mismatch").join("yt-dlp");
+        let server = TestServer::start();
+        server.serve("yt-dlp", b"tampered", "\"1\"");
+        let artifact = Artifact {
+            url: server.url("yt-dlp"),
+            ..artifact(&sha256(b"published"))
+        };
+
+        let result = download_to(&path, &artifact);
+
+        assert!(matches!(result, Err(DownloadError::ChecksumMismatch { .. })));
+        assert!(!path.exists());
+        assert!(!with_suffix(&path, DOWNLOAD_EXTENSION).exists());
+    }
+
+    /// Leaves `partial` as the interrupted download of `url`, started when the server sent `etag`.
+    fn interrupted_download(path: &Path, url: &str, partial: &[u8], etag: &str) {
+        std::fs::write(with_suffix(path, DOWNLOAD_EXTENSION), partial).unwrap();
+        let resumed = PartialDownload {
+            url: url.to_string(),
+            validator: etag.to_string(),
+        };
+        std::fs::write(with_suffix(path, RESUME_EXTENSION), serde_json::to_string(&resumed).unwrap()).unwrap();
+    }
+
+    fn numbered_bytes(len: usize, seed: u8) -> Vec<u8> {
+        (0..len).map(|index| (index as u8).wrapping_add(seed)).collect()
+    }
+
+    #[test]
+    fn resumes_interrupted_downloads() {
+        let path = test_dir("prerequisites-resume").join("yt-dlp");
+        let content = numbered_bytes(1000, 0);
+        let server = TestServer::start();
+        server.serve("yt-dlp", &content, "\"1\"");
+        let artifact = served_artifact(&server, "yt-dlp", &content);
+        interrupted_download(&path, &artifact.url, &content[..400], "\"1\"");
+
+        download_to(&path, &artifact).unwrap();
+
+        assert_eq!(server.ranges(), [Some(String::from("bytes=400-"))]);
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+        assert!(!with_suffix(&path, RESUME_EXTENSION).exists());
+    }
+
+    #[test]
+    fn starts_over_when_the_file_changed() {
+        let path = test_dir("prerequisites-changed").join("yt-dlp");
+        let content = numbered_bytes(1000, 1);
+        let server = TestServer::start();
+        server.serve("yt-dlp", &content, "\"2\"");
+        let artifact = served_artifact(&server, "yt-dlp", &content);
+        interrupted_download(&path, &artifact.url, &numbered_bytes(400, 0), "\"1\"");
+
+        download_to(&path, &artifact).unwrap();
+
+        assert_eq!(server.ranges(), [Some(String::from("bytes=400-"))]);
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+    }
+
+    #[test]
+    fn completes_downloads_interrupted_once_received() {
+        let path = test_dir("prerequisites-complete").join("yt-dlp");
+        let content = numbered_bytes(1000, 0);
+        let server = TestServer::start();
+        server.serve("yt-dlp", &content, "\"1\"");
+        let artifact = served_artifact(&server, "yt-dlp", &content);
+        interrupted_download(&path, &artifact.url, &content, "\"1\"");
+
+        download_to(&path, &artifact).unwrap();
+
+        assert_eq!(server.ranges(), [Some(String::from("bytes=1000-"))]);
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+    }
+
+    #[test]
+    fn downloads_nothing_without_a_published_digest() {
+        let path = test_dir("prerequisites-no-digest").join("yt-dlp");
+        let server = TestServer::start();
+        server.serve("yt-dlp", b"yt-dlp", "\"1\"");
+        let artifact = Artifact {
+            url: server.url("yt-dlp"),
+            ..artifact("")
+        };
+
+        let result = download_to(&path, &artifact);
+
+        assert!(matches!(result, Err(DownloadError::InvalidManifest(_))));
+        assert!(server.ranges().is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn rejects_missing_or_malformed_digests_as_manifest_errors() {
+        let checksum: Checksum = Sha256::digest(b"yt-dlp").into();
+        for sha256 in ["", "   ", "abc", &"g".repeat(64), &"a".repeat(65)] {
+            assert!(matches!(check(&artifact(sha256), &checksum), Err(DownloadError::InvalidManifest(_))));
+        }
+    }
+
+    #[test]
+    fn compares_digests_in_any_case() {
+        let checksum: Checksum = Sha256::digest(b"yt-dlp").into();
+        let sha256: String = checksum.iter().map(|byte| format!("{:02X}", byte)).collect();
+
+        assert!(check(&artifact(&sha256), &checksum).is_ok());
+        assert!(matches!(
+            check(&artifact(&"a".repeat(64)), &checksum),
+            Err(DownloadError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn installs_again_when_the_manifest_changes() {
+        let libs_folder = test_dir("prerequisites-installed");
+        let prerequisites = Prerequisites {
+            yt_dlp: artifact(&"a".repeat(64)),
+            ffmpeg: artifact(&"b".repeat(64)),
+        };
+        assert!(!prerequisites.is_installed(&libs_folder));
+
+        prerequisites.record_installed(&libs_folder).unwrap();
+        assert!(prerequisites.is_installed(&libs_folder));
+
+        let mut updated = prerequisites.clone();
+        updated.yt_dlp.version = String::from("2.0");
+        assert!(!updated.is_installed(&libs_folder));
+    }
+}
@@ -0,0 +1,499 @@
+use crate::yt::chapters::{Chapter, ChapterSplit};
+use crate::yt::loudness::Normalization;
+use crate::yt::progress::{DownloadEvent, DownloadPhase, DownloadProgress, ProgressReader};
+use crate::yt::verify::Verification;
+use crate::yt::{ArchiveCopy, DownloadError, filename, staging};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// A running yt-dlp process whose standard error is collected in the background,
+/// so that a chatty process can never block on a full pipe while its progress is being read.
+pub struct DownloadProcess {
+    child: Arc<Mutex<Child>>,
+    stdout: Option<ChildStdout>,
+    stderr: Option<JoinHandle<String>>,
+    cancel_handle: CancelHandle,
+    output_dir: Option<PathBuf>,
+    work_dir: Option<PathBuf>,
+    archive: Option<ArchiveCopy>,
+    normalization: Option<Normalization>,
+    chapter_split: Option<ChapterSplit>,
+    verification: Option<Verification>,
+    finished_files: Arc<Mutex<Vec<FinishedFile>>>,
+}
+
+/// An audio file written by yt-dlp, with the video it was extracted from.
+#[derive(Debug, Clone)]
+pub struct DownloadedFile {
+    pub id: String,
+    pub title: String,
+    pub path: PathBuf,
+}
+
+/// A file reported finished by yt-dlp, still in the work directory.
+struct FinishedFile {
+    file: DownloadedFile,
+    chapters: Vec<Chapter>,
+    name: String,
+}
+
+/// Stops a [`DownloadProcess`] from another thread. A handle can be shared by processes
+/// running one after the other, once cancelled it stops any process it gets attached to.
+#[derive(Clone, Debug, Default)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+    child: Arc<Mutex<Option<Arc<Mutex<Child>>>>>,
+}
+
+impl DownloadProcess {
+    pub fn new(mut child: Child) -> Self {
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut output = String::new();
+                let _ = stderr.read_to_string(&mut output);
+                output
+            })
+        });
+
+        let child = Arc::new(Mutex::new(child));
+        let cancel_handle = CancelHandle::default();
+        cancel_handle.attach(child.clone());
+
+        Self {
+            child,
+            stdout,
+            stderr,
+            cancel_handle,
+            output_dir: None,
+            work_dir: None,
+            archive: None,
+            normalization: None,
+            chapter_split: None,
+            verification: None,
+            finished_files: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// The finished files are moved to `output_dir`, nothing is written there before.
+    pub fn with_output_dir(mut self, output_dir: PathBuf) -> Self {
+        self.output_dir = Some(output_dir);
+        self
+    }
+
+    /// `work_dir` only holds the files of this process until they are finished, it is removed
+    /// once the process exits.
+    pub fn with_work_dir(mut self, work_dir: PathBuf) -> Self {
+        self.work_dir = Some(work_dir);
+        self
+    }
+
+    /// The videos recorded in `archive` by yt-dlp are added to the archive of the drive once
+    /// their files are all moved to the output directory.
+    pub fn with_archive(mut self, archive: ArchiveCopy) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+
+    /// The finished files are normalised before being moved to the output directory.
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    /// The finished files with chapters are split into one track per chapter, moved to a folder
+    /// of the output directory named after the video.
+    pub fn with_chapter_split(mut self, chapter_split: ChapterSplit) -> Self {
+        self.chapter_split = Some(chapter_split);
+        self
+    }
+
+    /// Each file moved to the output directory is read back and checked, a file failing the
+    /// verification is removed and fails the process.
+    pub fn with_verification(mut self, verification: Verification) -> Self {
+        self.verification = Some(verification);
+        self
+    }
+
+    pub fn with_cancel_handle(mut self, cancel_handle: CancelHandle) -> Self {
+        cancel_handle.attach(self.child.clone());
+        self.cancel_handle = cancel_handle;
+        self
+    }
+
+    /// Takes the progress events of the process, this can only be done once.
+    pub fn progress(&mut self) -> Option<impl Iterator<Item = DownloadProgress> + use<>> {
+        let finished_files = self.finished_files.clone();
+
+        self.stdout.take().map(move |stdout| {
+            ProgressReader::new(stdout).filter_map(move |event| match event {
+                DownloadEvent::Progress(progress) => Some(progress),
+                DownloadEvent::FileFinished { id, title, path, chapters, name } => {
+                    finished_files.lock().unwrap().push(FinishedFile {
+                        file: DownloadedFile { id, title, path },
+                        chapters,
+                        name,
+                    });
+                    None
+                }
+            })
+        })
+    }
+
+    /// Waits for the process and turns an unsuccessful exit into an error describing its cause.
+    /// On success, the finished files are moved to the output directory under their sanitized
+    /// names and returned, the copy being reported to `on_progress`.
+    pub fn wait_with_progress(
+        mut self,
+        mut on_progress: impl FnMut(DownloadProgress),
+    ) -> Result<Vec<DownloadedFile>, DownloadError> {
+        let status = wait_polling(&self.child)?;
+        let stderr = self
+            .stderr
+            .take()
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default();
+
+        let result = if self.cancel_handle.is_cancelled() {
+            Err(DownloadError::Cancelled)
+        } else if status.success() {
+            self.finish_files(&mut on_progress).inspect(|_| {
+                // Missing from the archive, the video is only downloaded again the next time
+                if let Some(archive) = &self.archive {
+                    let _ = archive.merge();
+                }
+            })
+        } else {
+            Err(parse_error(&stderr).unwrap_or(DownloadError::NonZeroExit {
+                program: String::from("yt-dlp"),
+                code: status.code(),
+            }))
+        };
+
+        // Partial files of a cancelled or failed download go with it
+        if let Some(work_dir) = &self.work_dir {
+            let _ = std::fs::remove_dir_all(work_dir);
+        }
+
+        result
+    }
+
+    fn finish_files(&self, on_progress: &mut impl FnMut(DownloadProgress)) -> Result<Vec<DownloadedFile>, DownloadError> {
+        let finished_files = std::mem::take(&mut *self.finished_files.lock().unwrap());
+        let mut on_copy_progress = |copied, total| {
+            on_progress(DownloadProgress {
+                phase: DownloadPhase::Copying,
+                downloaded_bytes: Some(copied),
+                total_bytes: Some(total),
+                ..DownloadProgress::default()
+            })
+        };
+
+        let mut files = Vec::with_capacity(finished_files.len());
+        for finished_file in finished_files {
+            if self.cancel_handle.is_cancelled() {
+                return Err(DownloadError::Cancelled);
+            }
+            files.extend(self.finish_file(finished_file, &mut on_copy_progress)?);
+        }
+
+        Ok(files)
+    }
+
+    /// Moves a finished file to the output directory, as one file or one per chapter.
+    fn finish_file(
+        &self,
+        FinishedFile { file, chapters, name }: FinishedFile,
+        on_copy_progress: &mut impl FnMut(u64, u64),
+    ) -> Result<Vec<DownloadedFile>, DownloadError> {
+        // The whole video is normalised at once, so that its tracks keep their relative volume
+        if let Some(normalization) = &self.normalization {
+            normalization.apply(&file.path, &self.cancel_handle)?;
+        }
+
+        let output_dir = self
+            .output_dir
+            .as_deref()
+            .or(file.path.parent())
+            .unwrap_or(Path::new(""));
+        let verification = self.verification.as_ref();
+        let cancel_handle = &self.cancel_handle;
+        let drive_lock = staging::drive_lock(output_dir);
+        match &self.chapter_split {
+            Some(chapter_split) if !chapters.is_empty() => {
+                let tracks = chapter_split.apply(&file.path, &chapters, &file.title, &self.cancel_handle)?;
+                let _copying = drive_lock.lock().unwrap();
+                let album_dir = output_dir.join(filename::sanitize(&name, ""));
+                std::fs::create_dir_all(&album_dir)?;
+                staging::remove_partial_files(&album_dir);
+
+                let mut files = Vec::with_capacity(tracks.len());
+                for (index, ((track, track_name), chapter)) in tracks.into_iter().zip(&chapters).enumerate() {
+                    let path = staging::move_to(
+                        &track,
+                        &album_dir,
+                        &track_name,
+                        verification,
+                        cancel_handle,
+                        on_copy_progress,
+                    )?;
+                    files.push(DownloadedFile {
+                        id: file.id.clone(),
+                        title: chapter.display_title(index + 1),
+                        path,
+                    });
+                }
+                Ok(files)
+            }
+            _ => {
+                let _copying = drive_lock.lock().unwrap();
+                let path =
+                    staging::move_to(&file.path, output_dir, &name, verification, cancel_handle, on_copy_progress)?;
+                Ok(vec![DownloadedFile { path, ..file }])
+            }
+        }
+    }
+}
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+
+        if let Some(child) = self.child.lock().unwrap().as_ref() {
+            kill(&mut child.lock().unwrap());
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Runs `command` to completion like [`Command::output`], the ffmpeg processes run once yt-dlp
+    /// exited are killed by a cancellation too.
+    pub(crate) fn output(&self, command: &mut Command) -> Result<Output, DownloadError> {
+        if self.is_cancelled() {
+            return Err(DownloadError::Cancelled);
+        }
+
+        let mut child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        let stdout = child.stdout.take().map(read_in_background);
+        let stderr = child.stderr.take().map(read_in_background);
+        let child = Arc::new(Mutex::new(child));
+        self.attach(child.clone());
+
+        let status = wait_polling(&child)?;
+        let collect = |handle: Option<JoinHandle<Vec<u8>>>| handle.and_then(|handle| handle.join().ok()).unwrap_or_default();
+        let output = Output {
+            status,
+            stdout: collect(stdout),
+            stderr: collect(stderr),
+        };
+
+        if self.is_cancelled() {
+            Err(DownloadError::Cancelled)
+        } else {
+            Ok(output)
+        }
+    }
+
+    fn attach(&self, child: Arc<Mutex<Child>>) {
+        let mut attached = self.child.lock().unwrap();
+        if self.is_cancelled() {
+            kill(&mut child.lock().unwrap());
+        }
+        *attached = Some(child);
+    }
+}
+
+/// Polling keeps the lock free for a cancellation coming from another thread.
+fn wait_polling(child: &Mutex<Child>) -> std::io::Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.lock().unwrap().try_wait()? {
+            return Ok(status);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn read_in_background(mut reader: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = reader.read_to_end(&mut output);
+        output
+    })
+}
+
+fn kill(child: &mut Child) {
+    if let Ok(None) = child.try_wait() {
+        kill_tree(child.id());
+        let _ = child.kill();
+    }
+}
+
+/// yt-dlp runs ffmpeg (and on Windows a second interpreter process), they must be stopped too.
+#[cfg(target_os = "windows")]
+fn kill_tree(pid: u32) {
+    let _ = super::new_command(Path::new("taskkill"))
+        .args(["/T", "/F", "/PID", pid.to_string().as_str()])
+        .status();
+}
+
+/// yt-dlp is started in its own process group, which also holds the ffmpeg processes it runs.
+#[cfg(not(target_os = "windows"))]
+fn kill_tree(pid: u32) {
+    let _ = super::new_command(Path::new("kill"))
+        .args(["-KILL", "--", format!("-{}", pid).as_str()])
+        // The ffmpeg processes run once yt-dlp exited have no group of their own
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Returns the standard output of a yt-dlp process run to completion, or the cause of its failure.
+pub(super) fn output_to_string(output: Output) -> Result<String, DownloadError> {
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(parse_error(&String::from_utf8_lossy(&output.stderr)).unwrap_or(
+            DownloadError::NonZeroExit {
+                program: String::from("yt-dlp"),
+                code: output.status.code(),
+            },
+        ))
+    }
+}
+
+/// Finds the last `ERROR:` line printed by yt-dlp and classifies it.
+fn parse_error(stderr: &str) -> Option<DownloadError> {
+    let message = stderr
+        .lines()
+        .rev()
+        .find_map(|line| line.trim().strip_prefix("ERROR:"))?
+        .trim();
+
+    if let Some(message) = message.strip_prefix("Postprocessing:") {
+        Some(DownloadError::Conversion(describe(message.trim())))
+    } else if message.contains("ffmpeg") {
+        Some(DownloadError::Conversion(describe(message)))
+    } else {
+        Some(DownloadError::Extraction(describe(message)))
+    }
+}
+
+/// Rewords the most common yt-dlp errors so that they can be understood by anyone.
+fn describe(message: &str) -> String {
+    const KNOWN_ERRORS: [(&str, &str); 6] = [
+        ("Private video", "cette vidéo est privée"),
+        ("Video unavailable", "cette vidéo n'est pas disponible"),
+        ("This video has been removed", "cette vidéo a été supprimée"),
+        ("Sign in to confirm your age", "cette vidéo est soumise à une limite d'âge"),
+        ("not available in your country", "cette vidéo n'est pas disponible dans votre pays"),
+        ("Unable to download webpage", "impossible de contacter YouTube, vérifiez votre connexion"),
+    ];
+
+    KNOWN_ERRORS
+        .iter()
+        .find(|(pattern, _)| message.contains(pattern))
+        .map(|(_, description)| description.to_string())
+        .unwrap_or_else(|| {
+            // Drop the "[extractor] video_id: " prefix, it means nothing to the user
+            match message.split_once("]") {
+                Some((_, rest)) if message.starts_with('[') => rest
+                    .split_once(": ")
+                    .map_or(rest, |(_, rest)| rest)
+                    .trim()
+                    .to_string(),
+                _ => message.to_string(),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_message(error: Option<DownloadError>) -> (bool, String) {
+        match error {
+            Some(DownloadError::Conversion(message)) => (true, message),
+            Some(DownloadError::Extraction(message)) => (false, message),
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_postprocessing_errors_as_conversions() {
+        let stderr = "WARNING: something\nERROR: Postprocessing: audio conversion failed: Error opening output files\n";
+        assert_eq!(
+            error_message(parse_error(stderr)),
+            (true, String::from("audio conversion failed: Error opening output files"))
+        );
+
+        let stderr = "ERROR: ffmpeg not found. Please install or provide the path using --ffmpeg-location";
+        assert!(error_message(parse_error(stderr)).0);
+    }
+
+    #[test]
+    fn rewords_known_errors() {
+        let stderr = "[youtube] abc: Downloading webpage\nERROR: [youtube] dQw4w9WgXcQ: Private video. Sign in if you've been granted access to this video\n";
+        assert_eq!(error_message(parse_error(stderr)), (false, String::from("cette vidéo est privée")));
+
+        let stderr = "ERROR: [youtube] dQw4w9WgXcQ: Video unavailable";
+        assert_eq!(error_message(parse_error(stderr)), (false, String::from("cette vidéo n'est pas disponible")));
+    }
+
+    #[test]
+    fn strips_the_extractor_and_video_prefix() {
+        let stderr = "ERROR: first\nERROR: [youtube] dQw4w9WgXcQ: Requested format is not available\n";
+        assert_eq!(error_message(parse_error(stderr)), (false, String::from("Requested format is not available")));
+
+        let stderr = "ERROR: [generic] Unsupported URL";
+        assert_eq!(error_message(parse_error(stderr)), (false, String::from("Unsupported URL")));
+
+        let stderr = "ERROR: Unable to extract data: Some detail";
+        assert_eq!(
+            error_message(parse_error(stderr)),
+            (false, String::from("Unable to extract data: Some detail"))
+        );
+    }
+
+    #[test]
+    fn ignores_output_without_error() {
+        assert!(parse_error("").is_none());
+        assert!(parse_error("WARNING: [youtube] nsig extraction failed\n").is_none());
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn cancelling_kills_the_commands_run_after_yt_dlp() {
+        let cancel_handle = CancelHandle::default();
+        let canceller = cancel_handle.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            canceller.cancel();
+        });
+
+        let start = std::time::Instant::now();
+        let result = cancel_handle.output(Command::new("sleep").arg("30"));
+        assert!(matches!(result, Err(DownloadError::Cancelled)), "{:?}", result);
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        // Nothing more is started once cancelled
+        let result = cancel_handle.output(&mut Command::new("true"));
+        assert!(matches!(result, Err(DownloadError::Cancelled)), "{:?}", result);
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn runs_commands_to_completion() {
+        let output = CancelHandle::default()
+            .output(Command::new("sh").args(["-c", "echo out; echo err >&2; exit 3"]))
+            .unwrap();
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+        assert_eq!(output.status.code(), Some(3));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::yt::chapters::Chapter;
+use std::io::{BufRead, BufReader, Lines, Read};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const DOWNLOAD_PREFIX: &str = "[smd-download]";
+const POSTPROCESS_PREFIX: &str = "[smd-postprocess]";
+const VIDEO_PREFIX: &str = "[smd-video] ";
+const FILEPATH_PREFIX: &str = "[smd-filepath] ";
+const CHAPTERS_PREFIX: &str = "[smd-chapters] ";
+const FILENAME_PREFIX: &str = "[smd-filename] ";
+
+/// Progress template used by yt-dlp while downloading, one whitespace separated line per update.
+pub const DOWNLOAD_TEMPLATE: &str = "download:[smd-download] %(progress.status)s %(progress.downloaded_bytes)s %(progress.total_bytes)s %(progress.total_bytes_estimate)s %(progress.speed)s %(progress.eta)s";
+/// Progress template used by yt-dlp while running its post-processors (audio extraction, tagging, ...).
+pub const POSTPROCESS_TEMPLATE: &str = "postprocess:[smd-postprocess] %(progress.status)s %(progress.postprocessor)s";
+
+/// `--print` templates making yt-dlp report each finished file, the video it comes from and its
+/// chapters, along with the name it should be given according to `output_template`.
+pub fn file_templates(output_template: &str) -> [String; 4] {
+    [
+        format!("after_move:{}%(id)s %(title)s", VIDEO_PREFIX),
+        format!("after_move:{}%(filepath)s", FILEPATH_PREFIX),
+        format!("after_move:{}%(chapters)j", CHAPTERS_PREFIX),
+        format!("after_move:{}{}", FILENAME_PREFIX, output_template),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DownloadPhase {
+    /// yt-dlp is still fetching the video information.
+    #[default]
+    Extracting,
+    Downloading,
+    Converting,
+    /// The finished file is being copied to the drive.
+    Copying,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DownloadProgress {
+    pub phase: DownloadPhase,
+    pub downloaded_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    /// Download speed in bytes per second.
+    pub speed: Option<f64>,
+    pub eta: Option<Duration>,
+}
+
+impl DownloadProgress {
+    /// Parses a line printed by yt-dlp with [`DOWNLOAD_TEMPLATE`] or [`POSTPROCESS_TEMPLATE`].
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+
+        if let Some(fields) = line.strip_prefix(DOWNLOAD_PREFIX) {
+            let fields: Vec<&str> = fields.split_whitespace().collect();
+            if fields.len() != 6 {
+                return None;
+            }
+
+            let phase = match fields[0] {
+                "finished" => DownloadPhase::Converting,
+                _ => DownloadPhase::Downloading,
+            };
+
+            Some(Self {
+                phase,
+                downloaded_bytes: parse_number(fields[1]).map(|n| n as u64),
+                total_bytes: parse_number(fields[2])
+                    .or_else(|| parse_number(fields[3]))
+                    .map(|n| n as u64),
+                speed: parse_number(fields[4]),
+                eta: parse_number(fields[5]).map(Duration::from_secs_f64),
+            })
+        } else if line.starts_with(POSTPROCESS_PREFIX) {
+            Some(Self {
+                phase: DownloadPhase::Converting,
+                ..Self::default()
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Progress of the current phase between 0 and 1, if it is known.
+    pub fn fraction(&self) -> Option<f64> {
+        match (self.phase, self.downloaded_bytes, self.total_bytes) {
+            (DownloadPhase::Downloading | DownloadPhase::Copying, Some(downloaded), Some(total)) if total > 0 => {
+                Some((downloaded as f64 / total as f64).clamp(0.0, 1.0))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// yt-dlp prints `NA` for the fields it does not know yet.
+fn parse_number(field: &str) -> Option<f64> {
+    field.parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadEvent {
+    Progress(DownloadProgress),
+    /// The file of the video `id` is complete at `path`, `name` is the output template rendered for it.
+    FileFinished {
+        id: String,
+        title: String,
+        path: PathBuf,
+        chapters: Vec<Chapter>,
+        name: String,
+    },
+}
+
+/// Turns the output of a yt-dlp process into a stream of [`DownloadEvent`],
+/// ignoring every line that is not printed by one of our templates.
+pub struct ProgressReader<R: Read> {
+    lines: Lines<BufReader<R>>,
+    finished_video: Option<(String, String)>,
+    finished_path: Option<PathBuf>,
+    finished_chapters: Vec<Chapter>,
+}
+
+impl<R: Read> ProgressReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: BufReader::new(reader).lines(),
+            finished_video: None,
+            finished_path: None,
+            finished_chapters: Vec::new(),
+        }
+    }
+}
+
+impl<R: Read> Iterator for ProgressReader<R> {
+    type Item = DownloadEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for line in self.lines.by_ref() {
+            let Ok(line) = line else {
+                continue;
+            };
+
+            if let Some(video) = line.strip_prefix(VIDEO_PREFIX) {
+                // The identifier never contains spaces, the title may
+                let (id, title) = video.split_once(' ').unwrap_or((video, ""));
+                self.finished_video = Some((id.to_string(), title.to_string()));
+            } else if let Some(path) = line.strip_prefix(FILEPATH_PREFIX) {
+                self.finished_path = Some(PathBuf::from(path));
+            } else if let Some(chapters) = line.strip_prefix(CHAPTERS_PREFIX) {
+                // A video without chapters prints `NA`
+                self.finished_chapters = serde_json::from_str::<Option<Vec<Chapter>>>(chapters)
+                    .ok()
+                    .flatten()
+                    .unwrap_or_default();
+            } else if let Some(name) = line.strip_prefix(FILENAME_PREFIX) {
+                let chapters = std::mem::take(&mut self.finished_chapters);
+                if let (Some((id, title)), Some(path)) = (self.finished_video.take(), self.finished_path.take()) {
+                    return Some(DownloadEvent::FileFinished {
+                        id,
+                        title,
+                        path,
+                        chapters,
+                        name: name.to_string(),
+                    });
+                }
+            } else if let Some(progress) = DownloadProgress::parse(&line) {
+                return Some(DownloadEvent::Progress(progress));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(output: &str) -> Vec<DownloadEvent> {
+        ProgressReader::new(output.as_bytes()).collect()
+    }
+
+    #[test]
+    fn parses_download_lines() {
+        let progress = DownloadProgress::parse("[smd-download] downloading 1024 4096 NA 512.5 6").unwrap();
+
+        assert_eq!(progress.phase, DownloadPhase::Downloading);
+        assert_eq!(progress.downloaded_bytes, Some(1024));
+        assert_eq!(progress.total_bytes, Some(4096));
+        assert_eq!(progress.speed, Some(512.5));
+        assert_eq!(progress.eta, Some(Duration::from_secs(6)));
+        assert_eq!(progress.fraction(), Some(0.25));
+    }
+
+    #[test]
+    fn leaves_unknown_fields_empty() {
+        let progress = DownloadProgress::parse("[smd-download] downloading NA NA 2048 NA NA").unwrap();
+
+        assert_eq!(progress.downloaded_bytes, None);
+        // The estimate stands in for the unknown total
+        assert_eq!(progress.total_bytes, Some(2048));
+        assert_eq!(progress.speed, None);
+        assert_eq!(progress.eta, None);
+        assert_eq!(progress.fraction(), None);
+
+        let progress = DownloadProgress::parse("[smd-download] downloading 10 NA NA NA NA").unwrap();
+        assert_eq!(progress.total_bytes, None);
+    }
+
+    #[test]
+    fn finished_downloads_are_converted() {
+        let progress = DownloadProgress::parse("[smd-download] finished 4096 4096 NA NA NA").unwrap();
+
+        assert_eq!(progress.phase, DownloadPhase::Converting);
+        assert_eq!(progress.fraction(), None);
+    }
+
+    #[test]
+    fn parses_postprocess_lines() {
+        let progress = DownloadProgress::parse("[smd-postprocess] started FFmpegExtractAudio").unwrap();
+
+        assert_eq!(progress, DownloadProgress {
+            phase: DownloadPhase::Converting,
+            ..DownloadProgress::default()
+        });
+    }
+
+    #[test]
+    fn ignores_other_lines() {
+        assert_eq!(DownloadProgress::parse("[youtube] abc: Downloading webpage"), None);
+        assert_eq!(DownloadProgress::parse("[smd-download] downloading 1024"), None);
+        assert!(events("WARNING: something\n[info] abc: Downloading 1 format(s)\n").is_empty());
+    }
+
+    #[test]
+    fn reports_finished_files() {
+        let events = events(concat!(
+            "[smd-download] downloading 10 20 NA NA NA\n",
+            "[smd-video] dQw4w9WgXcQ Never Gonna  Give You Up\n",
+            "[smd-filepath] /tmp/work/dQw4w9WgXcQ.mp3\n",
+            "[smd-chapters] [{\"start_time\": 0.0, \"end_time\": 30.5, \"title\": \"Intro\"}]\n",
+            "[smd-filename] Never Gonna Give You Up\n",
+        ));
+
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], DownloadEvent::Progress(_)));
+        assert_eq!(events[1], DownloadEvent::FileFinished {
+            id: String::from("dQw4w9WgXcQ"),
+            title: String::from("Never Gonna  Give You Up"),
+            path: PathBuf::from("/tmp/work/dQw4w9WgXcQ.mp3"),
+            chapters: vec![Chapter {
+                start_time: 0.0,
+                end_time: 30.5,
+                title: Some(String::from("Intro")),
+            }],
+            name: String::from("Never Gonna Give You Up"),
+        });
+    }
+
+    #[test]
+    fn videos_without_chapters_print_na() {
+        let events = events(concat!(
+            "[smd-video] abc Titre\n",
+            "[smd-filepath] /tmp/work/abc.opus\n",
+            "[smd-chapters] NA\n",
+            "[smd-filename] Titre\n",
+        ));
+
+        assert!(matches!(&events[..], [DownloadEvent::FileFinished { chapters, .. }] if chapters.is_empty()));
+    }
+
+    #[test]
+    fn ignores_file_names_without_their_file() {
+        let events = events(concat!(
+            "[smd-chapters] []\n",
+            "[smd-filename] Orphan\n",
+            "[smd-video] abc Titre\n",
+            "[smd-filepath] /tmp/work/abc.opus\n",
+            "[smd-filename] Titre\n",
+        ));
+
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], DownloadEvent::FileFinished { id, name, .. } if id == "abc" && name == "Titre"));
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt::{Display, Formatter};
+
+/// Segments of a video marked by SponsorBlock users, that yt-dlp can cut out of the audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SponsorBlockCategory {
+    MusicOfftopic,
+    Sponsor,
+    Intro,
+    Outro,
+    SelfPromo,
+    Interaction,
+}
+
+impl SponsorBlockCategory {
+    pub const ALL: [SponsorBlockCategory; 6] = [
+        SponsorBlockCategory::MusicOfftopic,
+        SponsorBlockCategory::Sponsor,
+        SponsorBlockCategory::Intro,
+        SponsorBlockCategory::Outro,
+        SponsorBlockCategory::SelfPromo,
+        SponsorBlockCategory::Interaction,
+    ];
+
+    /// Segments that are never music, removed unless the user says otherwise.
+    pub fn is_removed_by_default(&self) -> bool {
+        matches!(
+            self,
+            SponsorBlockCategory::MusicOfftopic
+                | SponsorBlockCategory::Sponsor
+                | SponsorBlockCategory::Intro
+                | SponsorBlockCategory::Outro
+        )
+    }
+
+    /// Name of the category in yt-dlp's `--sponsorblock-remove` argument.
+    pub fn as_arg(&self) -> &'static str {
+        match self {
+            SponsorBlockCategory::MusicOfftopic => "music_offtopic",
+            SponsorBlockCategory::Sponsor => "sponsor",
+            SponsorBlockCategory::Intro => "intro",
+            SponsorBlockCategory::Outro => "outro",
+            SponsorBlockCategory::SelfPromo => "selfpromo",
+            SponsorBlockCategory::Interaction => "interaction",
+        }
+    }
+}
+
+impl Display for SponsorBlockCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SponsorBlockCategory::MusicOfftopic => write!(f, "Passages sans musique"),
+            SponsorBlockCategory::Sponsor => write!(f, "Sponsors"),
+            SponsorBlockCategory::Intro => write!(f, "Introductions"),
+            SponsorBlockCategory::Outro => write!(f, "Fins et génériques"),
+            SponsorBlockCategory::SelfPromo => write!(f, "Autopromotion"),
+            SponsorBlockCategory::Interaction => write!(f, "Rappels d'abonnement"),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::yt::verify::{Checksum, Verification};
+use crate::yt::{CancelHandle, DownloadError, filename};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+const COPY_BUFFER_SIZE: usize = 1024 * 1024;
+const PARTIAL_FILE_PREFIX: &str = ".convertisseur-";
+const PARTIAL_FILE_EXTENSION: &str = "part";
+
+/// Lock held while copying to the drive mounted at `drive_path`. Parallel downloads are converted
+/// at the same time, but write to a drive one after the other: a USB drive is much slower when
+/// several files are written at once.
+pub fn drive_lock(drive_path: &Path) -> Arc<Mutex<()>> {
+    static DRIVE_LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = LazyLock::new(Default::default);
+
+    DRIVE_LOCKS.lock().unwrap().entry(drive_path.to_path_buf()).or_default().clone()
+}
+
+/// Moves a finished file from the local work directory into `directory` on the drive, named after
+/// the sanitized `name`. `on_progress` is given the bytes copied so far and the size of the file.
+/// A copy failing the `verification` is removed from the drive, a cancelled copy leaves nothing there.
+pub fn move_to(
+    path: &Path,
+    directory: &Path,
+    name: &str,
+    verification: Option<&Verification>,
+    cancel_handle: &CancelHandle,
+    on_progress: &mut impl FnMut(u64, u64),
+) -> Result<PathBuf, DownloadError> {
+    let (target, checksum) = copy_atomically(path, directory, name, cancel_handle, on_progress).map_err(|e| {
+        if cancel_handle.is_cancelled() {
+            DownloadError::Cancelled
+        } else {
+            DownloadError::from(e)
+        }
+    })?;
+    if let Some(verification) = verification
+        && let Err(e) = verification.apply(&target, &checksum, cancel_handle)
+    {
+        let _ = std::fs::remove_file(&target);
+        return Err(e);
+    }
+    std::fs::remove_file(path)?;
+
+    Ok(target)
+}
+
+/// Copies `source` into `directory` under the name reserved by [`filename::reserve_path`], so that
+/// the copy is either missing or complete, even when the drive is unplugged during the copy: the data
+/// goes to a hidden file first, which is flushed to the drive and only then renamed over the
+/// reserved name. Returns the path of the copy and the checksum of the copied data.
+fn copy_atomically(
+    source: &Path,
+    directory: &Path,
+    name: &str,
+    cancel_handle: &CancelHandle,
+    on_progress: &mut impl FnMut(u64, u64),
+) -> std::io::Result<(PathBuf, Checksum)> {
+    static NEXT_PARTIAL_FILE: AtomicU64 = AtomicU64::new(0);
+
+    // A short name, whatever the length of the final one
+    let partial = directory.join(format!(
+        "{}{}-{}.{}",
+        PARTIAL_FILE_PREFIX,
+        std::process::id(),
+        NEXT_PARTIAL_FILE.fetch_add(1, Ordering::Relaxed),
+        PARTIAL_FILE_EXTENSION
+    ));
+
+    let result = (|| {
+        let mut reader = File::open(source)?;
+        let total = reader.metadata()?.len();
+        let mut writer = File::create(&partial)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; COPY_BUFFER_SIZE];
+        let mut copied = 0;
+        on_progress(copied, total);
+        loop {
+            if cancel_handle.is_cancelled() {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read])?;
+            hasher.update(&buffer[..read]);
+            copied += read as u64;
+            on_progress(copied, total);
+        }
+        writer.sync_all()?;
+        drop(writer);
+
+        // Reserved only now, an interrupted copy must not leave an empty file under the final name
+        let target = filename::reserve_path(source, directory, name)?;
+        if let Err(e) = std::fs::rename(&partial, &target) {
+            let _ = std::fs::remove_file(&target);
+            return Err(e);
+        }
+        sync_directory(directory);
+        Ok((target, hasher.finalize().into()))
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Writes the new directory entry to the drive, which is not possible on Windows nor needed there.
+#[cfg(not(target_os = "windows"))]
+fn sync_directory(directory: &Path) {
+    if let Ok(directory) = File::open(directory) {
+        let _ = directory.sync_all();
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn sync_directory(_directory: &Path) {}
+
+/// Removes the partial files a previous copy left in `directory`, when the drive was unplugged or
+/// the application closed during the copy. Those of the running application are kept.
+pub fn remove_partial_files(directory: &Path) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    let own_prefix = format!("{}{}-", PARTIAL_FILE_PREFIX, std::process::id());
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(PARTIAL_FILE_PREFIX)
+            && !name.starts_with(&own_prefix)
+            && name.ends_with(&format!(".{}", PARTIAL_FILE_EXTENSION))
+        {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yt::test_dir;
+
+    fn downloaded_file(directory: &Path, name: &str, content: &str) -> PathBuf {
+        let path = directory.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn file_names(directory: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn never_overwrites_a_file_of_the_drive() {
+        let directory = test_dir("staging-existing");
+        let drive = directory.join("drive");
+        std::fs::create_dir_all(&drive).unwrap();
+        std::fs::write(drive.join("Titre.mp3"), "existing").unwrap();
+
+        let path = downloaded_file(&directory, "a.mp3", "downloaded");
+        let target = move_to(&path, &drive, "Titre", None, &CancelHandle::default(), &mut |_, _| {}).unwrap();
+
+        assert_eq!(target, drive.join("Titre (2).mp3"));
+        assert_eq!(std::fs::read_to_string(drive.join("Titre.mp3")).unwrap(), "existing");
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "downloaded");
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn parallel_copies_under_the_same_name_are_all_kept() {
+        let directory = test_dir("staging-parallel");
+        let drive = directory.join("drive");
+        std::fs::create_dir_all(&drive).unwrap();
+
+        let copies: Vec<_> = (0..8)
+            .map(|index| {
+                let path = downloaded_file(&directory, &format!("{}.mp3", index), &index.to_string());
+                let drive = drive.clone();
+                std::thread::spawn(move || {
+                    move_to(&path, &drive, "Titre", None, &CancelHandle::default(), &mut |_, _| {}).unwrap()
+                })
+            })
+            .collect();
+        let mut contents: Vec<String> = copies
+            .into_iter()
+            .map(|copy| std::fs::read_to_string(copy.join().unwrap()).unwrap())
+            .collect();
+        contents.sort();
+
+        assert_eq!(contents, (0..8).map(|index| index.to_string()).collect::<Vec<_>>());
+        assert_eq!(file_names(&drive).len(), 8);
+    }
+
+    #[test]
+    fn cancelled_copies_leave_nothing_on_the_drive() {
+        let directory = test_dir("staging-cancelled");
+        let drive = directory.join("drive");
+        std::fs::create_dir_all(&drive).unwrap();
+        let cancel_handle = CancelHandle::default();
+        cancel_handle.cancel();
+
+        let path = downloaded_file(&directory, "a.mp3", "downloaded");
+        let result = move_to(&path, &drive, "Titre", None, &cancel_handle, &mut |_, _| {});
+
+        assert!(matches!(result, Err(DownloadError::Cancelled)));
+        assert!(file_names(&drive).is_empty());
+        assert!(path.exists());
+    }
+}
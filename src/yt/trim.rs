@@ -0,0 +1,163 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// Part of a video to keep, either bound may be left open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrimRange {
+    start: Option<Duration>,
+    end: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimError {
+    InvalidStart,
+    InvalidEnd,
+    /// The end is not after the start.
+    Empty,
+    StartAfterDuration,
+    EndAfterDuration,
+}
+
+impl TrimRange {
+    /// Reads the timestamps typed by the user, an empty field leaves that bound open.
+    /// `None` when both bounds are open, the video is then downloaded whole.
+    pub fn parse(start: &str, end: &str) -> Result<Option<Self>, TrimError> {
+        let start = parse_optional_timestamp(start).ok_or(TrimError::InvalidStart)?;
+        let end = parse_optional_timestamp(end).ok_or(TrimError::InvalidEnd)?;
+        if let (Some(start), Some(end)) = (start, end)
+            && end <= start
+        {
+            return Err(TrimError::Empty);
+        }
+
+        // Trimming from the very beginning keeps the whole start
+        match (start.filter(|start| !start.is_zero()), end) {
+            (None, None) => Ok(None),
+            (start, end) => Ok(Some(Self { start, end })),
+        }
+    }
+
+    /// Checks the range against the duration of the video.
+    pub fn validate(&self, duration: Duration) -> Result<(), TrimError> {
+        if self.start.is_some_and(|start| start >= duration) {
+            Err(TrimError::StartAfterDuration)
+        } else if self.end.is_some_and(|end| end > duration) {
+            Err(TrimError::EndAfterDuration)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Value of yt-dlp's `--download-sections` argument, such as `*30-225.5` or `*30-inf`.
+    pub fn as_section_arg(&self) -> String {
+        let start = self.start.unwrap_or_default().as_secs_f64();
+        match self.end {
+            Some(end) => format!("*{}-{}", start, end.as_secs_f64()),
+            None => format!("*{}-inf", start),
+        }
+    }
+}
+
+impl Display for TrimError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrimError::InvalidStart => write!(f, "Début invalide, utilisez le format 1:30"),
+            TrimError::InvalidEnd => write!(f, "Fin invalide, utilisez le format 3:45"),
+            TrimError::Empty => write!(f, "La fin doit être après le début"),
+            TrimError::StartAfterDuration => write!(f, "Le début dépasse la durée de la vidéo"),
+            TrimError::EndAfterDuration => write!(f, "La fin dépasse la durée de la vidéo"),
+        }
+    }
+}
+
+impl std::error::Error for TrimError {}
+
+/// Reads `45`, `1:30`, `1:02:03` or `90.5`, the seconds may have a fraction.
+fn parse_timestamp(input: &str) -> Option<Duration> {
+    let components: Vec<&str> = input.trim().split(':').collect();
+    let (seconds, larger) = components.split_last()?;
+    if larger.len() > 2 || !seconds.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    let seconds: f64 = seconds.parse().ok()?;
+    if !larger.is_empty() && seconds >= 60.0 {
+        return None;
+    }
+
+    let mut total = seconds;
+    for (index, component) in larger.iter().rev().enumerate() {
+        if component.is_empty() || !component.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let component: u64 = component.parse().ok()?;
+        // Only the largest component may exceed its unit, `90:00` is an hour and a half
+        if index == 0 && larger.len() == 2 && component >= 60 {
+            return None;
+        }
+        total += component as f64 * 60f64.powi(index as i32 + 1);
+    }
+
+    // Too large a number of seconds does not fit in a duration
+    Duration::try_from_secs_f64(total).ok()
+}
+
+fn parse_optional_timestamp(input: &str) -> Option<Option<Duration>> {
+    if input.trim().is_empty() {
+        Some(None)
+    } else {
+        parse_timestamp(input).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seconds(seconds: f64) -> Option<Duration> {
+        Some(Duration::from_secs_f64(seconds))
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("45"), seconds(45.0));
+        assert_eq!(parse_timestamp(" 1:30 "), seconds(90.0));
+        assert_eq!(parse_timestamp("1:02:03"), seconds(3723.0));
+        assert_eq!(parse_timestamp("90.5"), seconds(90.5));
+        assert_eq!(parse_timestamp("1:05.25"), seconds(65.25));
+        assert_eq!(parse_timestamp("90:00"), seconds(5400.0));
+    }
+
+    #[test]
+    fn rejects_invalid_timestamps() {
+        for input in ["", "abc", "1:", ":30", "1:60", "1:60:00", "1:2:3:4", "-5", "1e3", "1.2.3", "."] {
+            assert_eq!(parse_timestamp(input), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_timestamps_too_large_for_a_duration() {
+        assert_eq!(parse_timestamp("99999999999999999999"), None);
+        assert_eq!(parse_timestamp("99999999999999999999:00:00"), None);
+        assert_eq!(parse_timestamp("9999999999999999:00:00"), None);
+        assert_eq!(TrimRange::parse("99999999999999999999", ""), Err(TrimError::InvalidStart));
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(TrimRange::parse("", " "), Ok(None));
+        assert_eq!(TrimRange::parse("0", ""), Ok(None));
+        assert_eq!(TrimRange::parse("0:00", "1:00"), Ok(Some(TrimRange { start: None, end: seconds(60.0) })));
+        assert_eq!(TrimRange::parse("0", "0"), Err(TrimError::Empty));
+        assert_eq!(TrimRange::parse("1:00", "0:30"), Err(TrimError::Empty));
+        assert_eq!(TrimRange::parse("1:00", "x"), Err(TrimError::InvalidEnd));
+
+        let range = TrimRange::parse("0:30", "3:45.5").unwrap().unwrap();
+        assert_eq!(range.as_section_arg(), "*30-225.5");
+        assert_eq!(range.validate(Duration::from_secs(200)), Err(TrimError::EndAfterDuration));
+        assert_eq!(range.validate(Duration::from_secs(300)), Ok(()));
+
+        let range = TrimRange::parse("30", "").unwrap().unwrap();
+        assert_eq!(range.as_section_arg(), "*30-inf");
+        assert_eq!(range.validate(Duration::from_secs(30)), Err(TrimError::StartAfterDuration));
+    }
+}
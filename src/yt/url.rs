@@ -0,0 +1,186 @@
+use reqwest::Url;
+
+const VIDEO_ID_LENGTH: usize = 11;
+
+/// A YouTube video and/or playlist, whatever the form of the link it was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YoutubeLink {
+    video_id: Option<String>,
+    playlist_id: Option<String>,
+}
+
+impl YoutubeLink {
+    /// Accepts `youtube.com`, `m.youtube.com`, `music.youtube.com` and `youtu.be` links,
+    /// with or without scheme, including `/shorts/`, `/embed/` and `/live/` links.
+    /// Unrelated query parameters such as `t` or `si` are dropped.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        let url = if input.contains("://") {
+            Url::parse(input).ok()?
+        } else {
+            Url::parse(&format!("https://{}", input)).ok()?
+        };
+        if url.scheme() != "https" && url.scheme() != "http" {
+            return None;
+        }
+
+        let host = url.host_str()?.to_ascii_lowercase();
+        let host = ["www.", "m.", "music."]
+            .iter()
+            .find_map(|prefix| host.strip_prefix(prefix))
+            .unwrap_or(&host);
+        let mut segments = url.path_segments()?.filter(|s| !s.is_empty());
+        let query = |key: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.into_owned())
+        };
+
+        let video_id = match (host, segments.next()) {
+            ("youtu.be", Some(id)) => Some(id.to_string()),
+            ("youtube.com", Some("watch")) => query("v"),
+            ("youtube.com", Some("shorts" | "embed" | "live" | "v")) => {
+                segments.next().map(str::to_string)
+            }
+            ("youtube.com", Some("playlist")) => None,
+            _ => return None,
+        };
+        // A malformed video identifier makes the whole link invalid, a malformed playlist is ignored
+        if video_id.as_deref().is_some_and(|id| !is_valid_video_id(id)) {
+            return None;
+        }
+        let playlist_id = query("list").filter(|id| is_valid_playlist_id(id));
+
+        if video_id.is_none() && playlist_id.is_none() {
+            None
+        } else {
+            Some(Self {
+                video_id,
+                playlist_id,
+            })
+        }
+    }
+
+    /// The link points to a video played from a playlist, the user may want either of them.
+    pub fn is_video_in_playlist(&self) -> bool {
+        self.video_id.is_some() && self.playlist_id.is_some()
+    }
+
+    pub fn video_id(&self) -> Option<&str> {
+        self.video_id.as_deref()
+    }
+
+    /// Canonical link to the video alone, without any playlist or timestamp.
+    pub fn video_url(&self) -> Option<String> {
+        self.video_id
+            .as_ref()
+            .map(|id| watch_url(id))
+    }
+
+    pub fn playlist_url(&self) -> Option<String> {
+        self.playlist_id
+            .as_ref()
+            .map(|id| format!("https://www.youtube.com/playlist?list={}", id))
+    }
+}
+
+/// Canonical link to the video `id` alone.
+pub fn watch_url(id: &str) -> String {
+    format!("https://www.youtube.com/watch?v={}", id)
+}
+
+fn is_valid_video_id(id: &str) -> bool {
+    id.len() == VIDEO_ID_LENGTH && id.chars().all(is_id_char)
+}
+
+fn is_valid_playlist_id(id: &str) -> bool {
+    id.len() >= 2 && id.chars().all(is_id_char)
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIDEO_URL: &str = "https://www.youtube.com/watch?v=dQw4w9WgXcQ";
+    const PLAYLIST_URL: &str = "https://www.youtube.com/playlist?list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG";
+
+    fn video_url(input: &str) -> Option<String> {
+        YoutubeLink::parse(input)?.video_url()
+    }
+
+    #[test]
+    fn parses_video_links() {
+        for input in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtube.com/watch?v=dQw4w9WgXcQ",
+            "http://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "  https://www.youtube.com/watch?v=dQw4w9WgXcQ\n",
+            "https://youtu.be/dQw4w9WgXcQ",
+            "youtu.be/dQw4w9WgXcQ?si=B_RZg_I-lLaa7UU-",
+            "https://m.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ&feature=share",
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            "https://www.youtube.com/embed/dQw4w9WgXcQ",
+            "https://www.youtube.com/live/dQw4w9WgXcQ?si=abc",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42s",
+            "https://WWW.YOUTUBE.COM/watch?t=42&v=dQw4w9WgXcQ",
+        ] {
+            assert_eq!(video_url(input).as_deref(), Some(VIDEO_URL), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parses_videos_in_playlists() {
+        let link = YoutubeLink::parse(
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG&index=2&t=10",
+        )
+        .unwrap();
+        assert!(link.is_video_in_playlist());
+        assert_eq!(link.video_id(), Some("dQw4w9WgXcQ"));
+        assert_eq!(link.video_url().as_deref(), Some(VIDEO_URL));
+        assert_eq!(link.playlist_url().as_deref(), Some(PLAYLIST_URL));
+
+        // A malformed playlist is ignored, the video is kept
+        let link = YoutubeLink::parse("https://youtu.be/dQw4w9WgXcQ?list=%20").unwrap();
+        assert!(!link.is_video_in_playlist());
+        assert_eq!(link.playlist_url(), None);
+    }
+
+    #[test]
+    fn parses_playlist_links() {
+        for input in [
+            "https://www.youtube.com/playlist?list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG",
+            "music.youtube.com/playlist?list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG&si=xyz",
+        ] {
+            let link = YoutubeLink::parse(input).unwrap();
+            assert_eq!(link.video_id(), None, "{:?}", input);
+            assert!(!link.is_video_in_playlist());
+            assert_eq!(link.playlist_url().as_deref(), Some(PLAYLIST_URL), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_other_links() {
+        for input in [
+            "",
+            "not a link",
+            "https://vimeo.com/watch?v=dQw4w9WgXcQ",
+            "https://www.youtube.com.evil.com/watch?v=dQw4w9WgXcQ",
+            "https://notyoutube.com/watch?v=dQw4w9WgXcQ",
+            "ftp://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXc",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQQ",
+            "https://youtu.be/dQw4w9WgX!Q",
+            "https://www.youtube.com/watch",
+            "https://www.youtube.com/playlist",
+            "https://www.youtube.com/@channel",
+        ] {
+            assert_eq!(YoutubeLink::parse(input), None, "{:?}", input);
+        }
+    }
+}
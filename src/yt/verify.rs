@@ -0,0 +1,120 @@
+use crate::yt::{CancelHandle, DownloadError, new_command};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const READ_BUFFER_SIZE: usize = 1024 * 1024;
+
+pub type Checksum = [u8; 32];
+
+/// Checks a file written to the drive: it is read back and compared with the staged copy, then
+/// decoded in full by ffmpeg. Cheap USB drives sometimes lose writes without any error.
+///
+/// The file must have been flushed to the drive. Its pages are then dropped from the page cache
+/// on Linux, and it is read without buffering on Windows, so that the data compared is the one
+/// the drive returns and not the one the copy left in memory. This catches data the drive did not
+/// store as written, not data it loses later, nor what a cache of the drive itself would hide.
+#[derive(Debug, Clone)]
+pub struct Verification {
+    pub ffmpeg_path: PathBuf,
+}
+
+impl Verification {
+    /// `expected` is the checksum of the staged copy of the file at `path`.
+    pub fn apply(&self, path: &Path, expected: &Checksum, cancel_handle: &CancelHandle) -> Result<(), DownloadError> {
+        if stored_checksum(path)? != *expected {
+            return Err(DownloadError::Verification(String::from(
+                "le fichier écrit sur la clé est différent de celui téléchargé",
+            )));
+        }
+
+        // Any decoding error is printed, and makes ffmpeg stop with -xerror
+        let output = cancel_handle.output(
+            new_command(&self.ffmpeg_path)
+                .args(["-hide_banner", "-nostdin", "-nostats", "-v", "error", "-xerror", "-i"])
+                .arg(path)
+                .args(["-map", "0:a", "-f", "null", "-"]),
+        )?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() || !stderr.trim().is_empty() {
+            let message = stderr.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+            return Err(DownloadError::Verification(format!(
+                "le fichier écrit sur la clé ne peut pas être lu entièrement : {}",
+                message.trim()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// SHA-256 of the file at `path` as read from the drive, bypassing the page cache.
+#[cfg(target_os = "linux")]
+fn stored_checksum(path: &Path) -> std::io::Result<Checksum> {
+    use std::os::fd::AsRawFd;
+
+    let file = File::open(path)?;
+    // Only the clean pages are dropped, the file was flushed when copied
+    let result = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    if result != 0 {
+        return Err(std::io::Error::from_raw_os_error(result));
+    }
+
+    read_checksum(file, &mut vec![0; READ_BUFFER_SIZE])
+}
+
+/// SHA-256 of the file at `path` as read from the drive, bypassing the page cache.
+#[cfg(target_os = "windows")]
+fn stored_checksum(path: &Path) -> std::io::Result<Checksum> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_FLAG_NO_BUFFERING: u32 = 0x20000000;
+    // Unbuffered reads go to a buffer aligned on the sector size, 4096 bytes at most
+    const SECTOR_ALIGNMENT: usize = 4096;
+
+    let file = File::options()
+        .read(true)
+        .custom_flags(FILE_FLAG_NO_BUFFERING)
+        .open(path)?;
+    let mut buffer = vec![0; READ_BUFFER_SIZE + SECTOR_ALIGNMENT];
+    let offset = buffer.as_ptr().align_offset(SECTOR_ALIGNMENT);
+
+    read_checksum(file, &mut buffer[offset..offset + READ_BUFFER_SIZE])
+}
+
+/// SHA-256 of the file at `path`.
+pub fn checksum(path: &Path) -> std::io::Result<Checksum> {
+    read_checksum(File::open(path)?, &mut vec![0; READ_BUFFER_SIZE])
+}
+
+fn read_checksum(mut file: File, buffer: &mut [u8]) -> std::io::Result<Checksum> {
+    let mut hasher = Sha256::new();
+    loop {
+        let read = file.read(buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yt::test_dir;
+
+    #[test]
+    fn reads_back_the_stored_file() {
+        let path = test_dir("verify").join("track.mp3");
+        let content: Vec<u8> = (0..3 * READ_BUFFER_SIZE + 17).map(|index| index as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+        File::open(&path).unwrap().sync_all().unwrap();
+
+        let expected: Checksum = Sha256::digest(&content).into();
+        assert_eq!(stored_checksum(&path).unwrap(), expected);
+        assert_eq!(checksum(&path).unwrap(), expected);
+    }
+}